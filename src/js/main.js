@@ -7,13 +7,21 @@ var cancelToken = new Object();
 var currentTurnNumber = 0;
 var turnRows = [];
 var turns = [];
+var positions = [];
+var startPlayerIndex = 0;
+var game = null;
+var searchToken = new Object();
 var wasmInitialized = false;
 var loaded = false;
 
+// How long each step of a minimax search runs for before the page gets control back.
+const SEARCH_STEP_MS = 10;
+
 window.addEventListener("DOMContentLoaded", onLoad);
 
 async function initializeWasm() {
     await init();
+    game = new Quoridor.Game();
     wasmInitialized = true;
     initialize();
 }
@@ -34,6 +42,8 @@ function onLoad() {
     document.getElementById("select-matrix-mode").addEventListener("change", redraw);
     document.getElementById("select-matrix-player").addEventListener("change", redraw);
     document.getElementById("select-matrix-text").addEventListener("change", redraw);
+    document.getElementById("btn-load-position").addEventListener("click", onLoadPosition);
+    document.getElementById("btn-copy-position").addEventListener("click", onCopyPosition);
 
     document.getElementById("turn-delay-slider").addEventListener("change", onDelayChange);
     document.getElementById("turn-delay-slider").addEventListener("input", onDelayChange);
@@ -60,7 +70,7 @@ function setTurnNumber(turnNumber) {
         document.getElementById("btn-back").classList.remove("pure-button-disabled");
     }
     if (currentTurnNumber == turns.length - 1) {
-        if (Quoridor.is_game_over()) {
+        if (game.is_game_over()) {
             document.getElementById("btn-forward").classList.add("pure-button-disabled");
         }
         document.getElementById("btn-end").classList.add("pure-button-disabled");
@@ -98,12 +108,12 @@ function onBack() {
     onJumpToTurn(currentTurnNumber - 1);
 }
 
-function onForward() {
+async function onForward() {
     if (currentTurnNumber == turns.length - 1) {
-        if (Quoridor.is_game_over()) {
+        if (game.is_game_over()) {
+            return;
+        } else if (!(await take_turn())) {
             return;
-        } else {
-            take_turn();
         }
     }
     onJumpToTurn(currentTurnNumber + 1);
@@ -120,7 +130,7 @@ function insertTurnRow(turnNumber, message) {
         row.insertCell(2).innerHTML = message;
     } else {
         var div = document.createElement("div");
-        div.className = turnNumber % 2 == 1 ? "player-chip-1" : "player-chip-2";
+        div.className = (startPlayerIndex + turnNumber) % 2 == 1 ? "player-chip-1" : "player-chip-2";
         row.insertCell(0).innerHTML = turnNumber;
         row.insertCell(1).appendChild(div);
         row.insertCell(2).innerHTML = message;
@@ -129,13 +139,14 @@ function insertTurnRow(turnNumber, message) {
     turnRows.push(row);
 }
 
-function take_turn() {
-    let playerIndex = currentTurnNumber % 2;
+// Returns false if the turn was cancelled before it was taken.
+async function take_turn() {
+    let playerIndex = (startPlayerIndex + currentTurnNumber) % 2;
     let player = playerIndex == 0 
         ? document.getElementById("select-player1").value
         : document.getElementById("select-player2").value;
 
-    let actionJson = null;
+    let action = null;
     if (player.startsWith("minimax")) {
         let depth = 0;
         if (player.endsWith("1")) {
@@ -145,27 +156,60 @@ function take_turn() {
         } else if (player.endsWith("3")) {
             depth = 3;
         }
-        actionJson = Quoridor.take_minimax_turn(playerIndex, depth)
+        action = await searchTurn(playerIndex, depth);
+        if (action == null) {
+            return false;
+        }
     }
     else if (player.startsWith("shortest-path")) {
-        actionJson = Quoridor.take_shortest_path_turn(playerIndex, 0.5)
+        action = game.take_shortest_path_turn(playerIndex, 0.5)
     } else {
-        actionJson = Quoridor.take_random_turn(playerIndex, 0.5)
+        action = game.take_random_turn(playerIndex, 0.5)
     }
-    let action = JSON.parse(actionJson);
-    let boardState = JSON.parse(Quoridor.get_board());
+    let boardState = game.get_board();
     turns.push(boardState)
+    positions.push(game.get_position(1 - playerIndex));
 
     let formattedAction = ("Move" in action) 
         ? `Move (${action.Move.x}, ${action.Move.y})`
         : `Block (${action.Block[0].x}, ${action.Block[0].y}) ${action.Block[1]}`;
     insertTurnRow(currentTurnNumber + 1, formattedAction);
+    return true;
+}
+
+// Runs the search a step per frame so that the controls keep working while the bot thinks.
+// Returns null if the search is cancelled.
+async function searchTurn(playerIndex, depth) {
+    cancelSearch();
+    let token = new Object();
+    token.isCancelled = false;
+    searchToken = token;
+    game.start_search(playerIndex, depth);
+    while (!game.advance_search_for(SEARCH_STEP_MS).is_finished) {
+        await new Promise(resolve => requestAnimationFrame(resolve));
+        if (token.isCancelled) {
+            return null;
+        }
+    }
+    return game.finish_search();
+}
+
+function cancelSearch() {
+    searchToken.isCancelled = true;
+    game.cancel_search();
 }
 
 async function gameLoop(cancelToken) {
-    while (!(currentTurnNumber == turns.length - 1 && Quoridor.is_game_over())) {
+    while (!(currentTurnNumber == turns.length - 1 && game.is_game_over())) {
         if (currentTurnNumber == turns.length - 1) {
-            take_turn();
+            try {
+                if (!(await take_turn())) {
+                    return;
+                }
+            } catch (error) {
+                alert(`The turn failed: ${error.message}`);
+                break;
+            }
         }
 
         setTurnNumber(currentTurnNumber + 1);
@@ -192,14 +236,39 @@ function setIsPlaying(value) {
         gameLoop(cancelToken);
     } else {
         cancelToken.isCancelled = true;
+        cancelSearch();
     }
 }
 
 function resetGame() {
+    game.reset_board();
+    startGame(0);
+}
+
+function onLoadPosition() {
+    let position = document.getElementById("position-value").value.trim();
+    let playerIndex;
+    try {
+        playerIndex = game.load_position(position);
+    } catch (error) {
+        alert(`Invalid position: ${error.message}`);
+        return;
+    }
+    startGame(playerIndex);
+}
+
+function onCopyPosition() {
+    navigator.clipboard.writeText(positions[currentTurnNumber]);
+}
+
+// Starts a new game from the board currently held by the wasm module.
+function startGame(playerIndex) {
     setIsPlaying(false);
+    startPlayerIndex = playerIndex;
 
     // Reset turn information
     turns = []
+    positions = [];
     turnRows = [];
     let turnTableBody = document.getElementById("turn-table-body");
     while (turnTableBody.hasChildNodes()) {
@@ -208,9 +277,9 @@ function resetGame() {
     insertTurnRow(0, "Start");
     setTurnNumber(0);
 
-    Quoridor.reset_board();
-    let boardState = JSON.parse(Quoridor.get_board());
+    let boardState = game.get_board();
     turns.push(boardState)
+    positions.push(game.get_position(playerIndex));
     redraw();    
 }
 
@@ -227,6 +296,7 @@ function redraw() {
     }
 
     let boardState = turns[currentTurnNumber];
+    document.getElementById("position-value").value = positions[currentTurnNumber];
     Rendering.setBoardState(boardState);
     Rendering.render();
 }
@@ -1,15 +1,26 @@
-use crate::board_state::BoardState;
+use crate::board_state::{BoardState, WallUndoStack};
 use crate::vector2::Vector2;
 use crate::wall_orientation::WallOrientation;
 use serde::Serialize;
 use std::fmt;
+use tsify::Tsify;
 
-#[derive(Copy, Clone, Serialize)]
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Tsify)]
 pub enum Action {
     Move(Vector2<isize>),
     Block(Vector2<isize>, WallOrientation),
 }
 
+// What `Action::make` changed, so that the search can take the action back instead of keeping a
+// copy of the board for every node. The distances changed by a wall are kept on the undo stack
+// shared by every record, from `change_start` on.
+pub struct UndoRecord {
+    action: Action,
+    player_index: usize,
+    previous_position: Vector2<isize>,
+    change_start: usize,
+}
+
 impl Action {
     pub fn apply(&self, board_state: &mut BoardState, player_index: usize) {
         match self {
@@ -21,6 +32,72 @@ impl Action {
             }
         };
     }
+
+    // Applies the action in place and returns what is needed to take it back with
+    // `UndoRecord::unmake`. Records must be unmade in the reverse order they were made.
+    pub fn make(
+        &self,
+        board_state: &mut BoardState,
+        player_index: usize,
+        undo_stack: &mut WallUndoStack,
+    ) -> UndoRecord {
+        let record = UndoRecord {
+            action: *self,
+            player_index,
+            previous_position: board_state.get_player_position(player_index),
+            change_start: undo_stack.len(),
+        };
+        match self {
+            Action::Move(position) => board_state.set_player_position(player_index, *position),
+            Action::Block(position, orientation) => {
+                board_state.set_wall_with_undo(*position, *orientation, undo_stack);
+                board_state.set_player_wall(*position, player_index);
+                board_state.set_player_wall_count(player_index, board_state.get_player_wall_count(player_index) - 1);
+            }
+        };
+        record
+    }
+
+    // Packs the action into a single byte. Moves occupy indices 0-80 (one per cell) and blocks
+    // occupy indices 81-208 (one per wall position and orientation).
+    pub fn to_index(self) -> u8 {
+        match self {
+            Action::Move(position) => (position.x * 9 + position.y) as u8,
+            Action::Block(position, orientation) => (81 + (position.x * 8 + position.y) * 2) as u8 + orientation as u8,
+        }
+    }
+
+    pub fn from_index(index: u8) -> Option<Action> {
+        let index = index as isize;
+        if index < 81 {
+            Some(Action::Move(Vector2::new(index / 9, index % 9)))
+        } else if index < 81 + 128 {
+            let wall_index = (index - 81) / 2;
+            let orientation = if (index - 81) % 2 == 0 {
+                WallOrientation::Vertical
+            } else {
+                WallOrientation::Horizontal
+            };
+            Some(Action::Block(Vector2::new(wall_index / 8, wall_index % 8), orientation))
+        } else {
+            None
+        }
+    }
+}
+
+impl UndoRecord {
+    pub fn unmake(self, board_state: &mut BoardState, undo_stack: &mut WallUndoStack) {
+        match self.action {
+            Action::Move(_) => board_state.set_player_position(self.player_index, self.previous_position),
+            Action::Block(position, _) => {
+                board_state.remove_wall(position, undo_stack, self.change_start);
+                board_state.set_player_wall_count(
+                    self.player_index,
+                    board_state.get_player_wall_count(self.player_index) + 1,
+                );
+            }
+        };
+    }
 }
 
 impl fmt::Display for Action {
@@ -36,6 +113,11 @@ impl fmt::Display for Action {
 mod tests {
     use super::*;
     use crate::board_state::BoardState;
+    use crate::notation::parse_position;
+    use crate::validation::get_legal_actions;
+    use rand::rngs::StdRng;
+    use rand::seq::SliceRandom;
+    use rand::SeedableRng;
 
     #[test]
     fn apply_move() {
@@ -64,4 +146,73 @@ mod tests {
         );
         assert_eq!(9, board_state.get_player_wall_count(1));
     }
+
+    #[test]
+    fn unmake_restores_board() {
+        let (start, _) = parse_position("d4hE6Vc2v.e3e7.8.9.1").unwrap();
+        let actions = [
+            Action::Move(Vector2::new(4, 3)),
+            Action::Block(Vector2::new(4, 2), WallOrientation::Horizontal),
+            Action::Block(Vector2::new(2, 4), WallOrientation::Vertical),
+        ];
+        for action in actions.iter() {
+            for player_index in 0..2 {
+                let mut board_state = start;
+                let mut undo_stack = WallUndoStack::default();
+
+                let record = action.make(&mut board_state, player_index, &mut undo_stack);
+                assert_eq!(true, start.from_action(action, player_index) == board_state);
+                record.unmake(&mut board_state, &mut undo_stack);
+
+                assert_eq!(true, start == board_state, "{}\n{}", action, board_state);
+                assert_eq!(Vec::<String>::new(), board_state.check_invariants());
+            }
+        }
+    }
+
+    // Walls are taken back in the reverse order they were placed, as they are in a search.
+    #[test]
+    fn unmake_restores_random_games() {
+        let mut rng = StdRng::seed_from_u64(0);
+        for _ in 0..50 {
+            let mut board_state = BoardState::new();
+            let mut history = Vec::new();
+            let mut undo_stack = WallUndoStack::default();
+            let mut player_index = 0;
+            loop {
+                let actions = get_legal_actions(&board_state, player_index);
+                let action = match actions.choose(&mut rng) {
+                    Some(action) => *action,
+                    None => break,
+                };
+                history.push((
+                    board_state,
+                    action.make(&mut board_state, player_index, &mut undo_stack),
+                ));
+                player_index = 1 - player_index;
+            }
+            while let Some((expected, record)) = history.pop() {
+                record.unmake(&mut board_state, &mut undo_stack);
+                assert_eq!(true, expected == board_state, "{}", board_state);
+                assert_eq!(Vec::<String>::new(), board_state.check_invariants());
+            }
+            assert_eq!(true, undo_stack.is_empty());
+        }
+    }
+
+    #[test]
+    fn index_round_trip() {
+        for index in 0..209 {
+            let action = Action::from_index(index).unwrap();
+            assert_eq!(index, action.to_index());
+        }
+        assert_eq!(None, Action::from_index(209));
+    }
+
+    #[test]
+    fn index_of_block() {
+        let action = Action::Block(Vector2::new(7, 7), WallOrientation::Horizontal);
+
+        assert_eq!(208, action.to_index());
+    }
 }
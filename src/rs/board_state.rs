@@ -1,9 +1,15 @@
 use crate::action::Action;
+use crate::notation::{format_action, format_cell};
+use crate::validation::is_wall_overlapping;
 use crate::vector2::Vector2;
 use crate::wall_orientation::WallOrientation;
+use crate::zobrist::ZOBRIST_KEYS;
 
 use serde::Serialize;
 use std::collections::VecDeque;
+use std::fmt;
+use std::str::FromStr;
+use tsify::Tsify;
 
 const RIGHT: usize = 0;
 const UP: usize = 1;
@@ -18,13 +24,47 @@ lazy_static! {
     ];
 }
 
-#[derive(Copy, Clone, Serialize)]
+// A distance that was changed by placing a wall, so that it can be put back when the wall is
+// removed.
+struct DistanceChange {
+    player_index: usize,
+    cell: Vector2<isize>,
+    distance: isize,
+}
+
+// The distances changed by each wall placed with `set_wall_with_undo` that hasn't been removed
+// yet, most recent last. It also holds the queues used to update the distances, so that a search
+// placing and removing walls through one stack doesn't allocate once the buffers have grown.
+#[derive(Default)]
+pub struct WallUndoStack {
+    changes: Vec<DistanceChange>,
+    downstream: VecDeque<Vector2<isize>>,
+    upstream: VecDeque<Vector2<isize>>,
+}
+
+impl WallUndoStack {
+    pub fn len(&self) -> usize {
+        self.changes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+}
+
+// The grids are described to TypeScript as nested arrays rather than as fixed length tuples, which
+// would be too long to read.
+#[derive(Copy, Clone, PartialEq, Serialize, Tsify)]
 pub struct BoardState {
+    #[tsify(type = "(WallOrientation | null)[][]")]
     pub walls: [[Option<WallOrientation>; 8]; 8],
+    #[tsify(type = "boolean[][][]")]
     pub cell_connections: [[[bool; 4]; 9]; 9],
     pub player_positions: [Vector2<isize>; 2],
     pub player_wall_counts: [usize; 2],
+    #[tsify(type = "number[][]")]
     pub player_walls: [[isize; 8]; 8],
+    #[tsify(type = "number[][][]")]
     pub distance_matrices: [[[isize; 9]; 9]; 2],
 }
 
@@ -66,22 +106,52 @@ impl BoardState {
     }
 
     pub fn set_wall(&mut self, position: Vector2<isize>, value: WallOrientation) {
+        self.set_wall_with_undo(position, value, &mut WallUndoStack::default());
+    }
+
+    // Places a wall and pushes each distance it changes onto the stack, so that `remove_wall` can
+    // take it back without recalculating the distances.
+    pub fn set_wall_with_undo(
+        &mut self,
+        position: Vector2<isize>,
+        value: WallOrientation,
+        undo_stack: &mut WallUndoStack,
+    ) {
+        self.walls[position.x as usize][position.y as usize] = Some(value);
+        self.set_wall_connections(position, value, false);
+        self.update_distance_matrix(0, position, undo_stack);
+        self.update_distance_matrix(1, position, undo_stack);
+    }
+
+    // Takes back the most recent wall placed with `set_wall_with_undo`, along with its owner.
+    // `change_start` is the length of the stack before the wall was placed. Walls can't overlap,
+    // so none of the connections it blocked are also blocked by another wall.
+    pub fn remove_wall(&mut self, position: Vector2<isize>, undo_stack: &mut WallUndoStack, change_start: usize) {
+        if let Some(value) = self.get_wall(position) {
+            self.walls[position.x as usize][position.y as usize] = None;
+            self.player_walls[position.x as usize][position.y as usize] = -1;
+            self.set_wall_connections(position, value, true);
+        }
+        for change in undo_stack.changes.drain(change_start..) {
+            self.distance_matrices[change.player_index][change.cell.x as usize][change.cell.y as usize] =
+                change.distance;
+        }
+    }
+
+    fn set_wall_connections(&mut self, position: Vector2<isize>, value: WallOrientation, is_open: bool) {
         let x = position.x as usize;
         let y = position.y as usize;
-        self.walls[x][y] = Some(value);
         if value == WallOrientation::Horizontal {
-            self.cell_connections[x][y][UP] = false;
-            self.cell_connections[x][y + 1][DOWN] = false;
-            self.cell_connections[x + 1][y][UP] = false;
-            self.cell_connections[x + 1][y + 1][DOWN] = false;
+            self.cell_connections[x][y][UP] = is_open;
+            self.cell_connections[x][y + 1][DOWN] = is_open;
+            self.cell_connections[x + 1][y][UP] = is_open;
+            self.cell_connections[x + 1][y + 1][DOWN] = is_open;
         } else if value == WallOrientation::Vertical {
-            self.cell_connections[x][y][RIGHT] = false;
-            self.cell_connections[x + 1][y][LEFT] = false;
-            self.cell_connections[x][y + 1][RIGHT] = false;
-            self.cell_connections[x + 1][y + 1][LEFT] = false;
+            self.cell_connections[x][y][RIGHT] = is_open;
+            self.cell_connections[x + 1][y][LEFT] = is_open;
+            self.cell_connections[x][y + 1][RIGHT] = is_open;
+            self.cell_connections[x + 1][y + 1][LEFT] = is_open;
         }
-        self.update_distance_matrix(0, position);
-        self.update_distance_matrix(1, position);
     }
 
     pub fn set_player_wall(&mut self, position: Vector2<isize>, player_index: usize) {
@@ -121,6 +191,149 @@ impl BoardState {
         return self.distance_matrices[player_index];
     }
 
+    // Draws the board with each cell showing the player's distance from that cell to their goal.
+    pub fn display_with_distances(&self, player_index: usize) -> BoardDisplay<'_> {
+        BoardDisplay {
+            board_state: self,
+            distance_player_index: Some(player_index),
+        }
+    }
+
+    // Hashes everything that affects play from this position onwards. Wall ownership is not
+    // included since it has no effect on the remainder of the game.
+    pub fn get_position_hash(&self, player_index: usize) -> u64 {
+        let keys = &*ZOBRIST_KEYS;
+        let mut hash = 0;
+        for x in 0..8 {
+            for y in 0..8 {
+                if let Some(orientation) = self.walls[x][y] {
+                    hash ^= keys.walls[x][y][orientation as usize];
+                }
+            }
+        }
+        for i in 0..2 {
+            let position = self.player_positions[i];
+            hash ^= keys.player_positions[i][position.x as usize][position.y as usize];
+            hash ^= keys.player_wall_counts[i][self.player_wall_counts[i]];
+        }
+        if player_index == 1 {
+            hash ^= keys.player_to_move;
+        }
+        hash
+    }
+
+    // Rebuilds the connections and distance matrices from the walls and compares them with the
+    // stored ones, along with the wall owners, wall counts and pawns. Returns a description of
+    // each mismatch found.
+    pub fn check_invariants(&self) -> Vec<String> {
+        const DIRECTION_NAMES: [&str; 4] = ["right", "up", "left", "down"];
+        let mut errors = Vec::new();
+
+        let mut rebuilt = *self;
+        let has_wall = |x: isize, y: isize, orientation: WallOrientation| {
+            BoardState::is_wall_index_in_bounds(Vector2::new(x, y))
+                && self.get_wall(Vector2::new(x, y)) == Some(orientation)
+        };
+        for x in 0..9 {
+            for y in 0..9 {
+                // A wall blocks the two cells on either side of each of its halves.
+                let is_open = [
+                    x < 8
+                        && !has_wall(x, y, WallOrientation::Vertical)
+                        && !has_wall(x, y - 1, WallOrientation::Vertical),
+                    y < 8
+                        && !has_wall(x, y, WallOrientation::Horizontal)
+                        && !has_wall(x - 1, y, WallOrientation::Horizontal),
+                    x > 0
+                        && !has_wall(x - 1, y, WallOrientation::Vertical)
+                        && !has_wall(x - 1, y - 1, WallOrientation::Vertical),
+                    y > 0
+                        && !has_wall(x, y - 1, WallOrientation::Horizontal)
+                        && !has_wall(x - 1, y - 1, WallOrientation::Horizontal),
+                ];
+                for i in 0..4 {
+                    let stored = self.cell_connections[x as usize][y as usize][i];
+                    if stored != is_open[i] {
+                        errors.push(format!(
+                            "Connection {} {} is {} but should be {}",
+                            format_cell(Vector2::new(x, y)),
+                            DIRECTION_NAMES[i],
+                            if stored { "open" } else { "blocked" },
+                            if is_open[i] { "open" } else { "blocked" }
+                        ));
+                    }
+                }
+                rebuilt.cell_connections[x as usize][y as usize] = is_open;
+            }
+        }
+
+        for (i, &row) in [8, 0].iter().enumerate() {
+            let matrix = rebuilt.calculate_distance_matrix(row);
+            for (x, column) in matrix.iter().enumerate() {
+                for (y, &expected) in column.iter().enumerate() {
+                    let stored = self.distance_matrices[i][x][y];
+                    if stored != expected {
+                        errors.push(format!(
+                            "Distance from {} for player {} is {} but should be {}",
+                            format_cell(Vector2::new(x as isize, y as isize)),
+                            i + 1,
+                            stored,
+                            expected
+                        ));
+                    }
+                }
+            }
+        }
+
+        let mut placed_counts = [0; 2];
+        for x in 0..8 {
+            for y in 0..8 {
+                let position = Vector2::new(x, y);
+                let owner = self.player_walls[x as usize][y as usize];
+                if let Some(orientation) = self.get_wall(position) {
+                    let action = Action::Block(position, orientation);
+                    let mut others = *self;
+                    others.walls[x as usize][y as usize] = None;
+                    if is_wall_overlapping(&others, position, orientation) {
+                        errors.push(format!("Wall {} overlaps another wall", format_action(&action)));
+                    }
+                    if owner == 0 || owner == 1 {
+                        placed_counts[owner as usize] += 1;
+                    } else if owner != -1 {
+                        errors.push(format!("Wall {} has unknown owner {}", format_action(&action), owner));
+                    }
+                } else if owner != -1 {
+                    errors.push(format!(
+                        "Empty wall position {} has owner {}",
+                        format_cell(position),
+                        owner
+                    ));
+                }
+            }
+        }
+        for (i, &placed_count) in placed_counts.iter().enumerate() {
+            if self.player_wall_counts[i] + placed_count > 10 {
+                errors.push(format!(
+                    "Player {} has {} walls left and {} on the board",
+                    i + 1,
+                    self.player_wall_counts[i],
+                    placed_count
+                ));
+            }
+            if !BoardState::is_cell_index_in_bounds(self.player_positions[i]) {
+                errors.push(format!(
+                    "Player {} is off the board at {}",
+                    i + 1,
+                    self.player_positions[i]
+                ));
+            }
+        }
+        if self.player_positions[0] == self.player_positions[1] {
+            errors.push(String::from("Both players are on the same cell"));
+        }
+        errors
+    }
+
     fn calculate_distance_matrix(&self, row: usize) -> [[isize; 9]; 9] {
         let mut matrix = [[-1; 9]; 9];
         let mut queue: VecDeque<Vector2<isize>> = VecDeque::new();
@@ -145,14 +358,20 @@ impl BoardState {
         return matrix;
     }
 
-    fn update_distance_matrix(&mut self, player_index: usize, new_wall: Vector2<isize>) {
+    fn update_distance_matrix(
+        &mut self,
+        player_index: usize,
+        new_wall: Vector2<isize>,
+        undo_stack: &mut WallUndoStack,
+    ) {
         // Cells are added to the downstream queue that are found to be potentially affected
         // by the new wall placement.
-        let mut downstream: VecDeque<Vector2<isize>> = VecDeque::new();
+        let downstream = &mut undo_stack.downstream;
         // Cells are added to the upstream queue that are adjacent to cells that have been added
         // to the downstream queue. These are later used to refill the distance values once all
         // downstream cells have been identified.
-        let mut upstream: VecDeque<Vector2<isize>> = VecDeque::new();
+        let upstream = &mut undo_stack.upstream;
+        let changes = &mut undo_stack.changes;
 
         // Create vectors for the four potentially affected cells.
         let bottom_left = Vector2::new(new_wall.x, new_wall.y);
@@ -209,6 +428,12 @@ impl BoardState {
             if distance == 0 || distance == -1 {
                 continue;
             }
+            // Only these cells get new distances, so they are all that has to be restored.
+            changes.push(DistanceChange {
+                player_index,
+                cell,
+                distance,
+            });
             self.distance_matrices[player_index][cell.x as usize][cell.y as usize] = -1;
             for i in 0..4 {
                 if self.cell_connections[cell.x as usize][cell.y as usize][i] {
@@ -254,9 +479,237 @@ impl BoardState {
     }
 }
 
+// Draws the board as text with the first player's goal row at the bottom. Pawns are drawn as
+// their player number, and walls as - and | along the grid lines they block.
+//
+//     a   b   c   d   e   f   g   h   i
+//   +-----------------------------------+
+// 9 | .   .   .   .   2   .   .   .   . | 9
+//   |                                   |
+//   ...
+// 3 | .   .   .   .   .   .   .   .   . | 3
+//   |            -------                |
+// 2 | .   .   .   .   .   . | .   .   . | 2
+//   |                       |           |
+// 1 | .   .   .   .   1   . | .   .   . | 1
+//   +-----------------------------------+
+//     a   b   c   d   e   f   g   h   i
+// Player 1: e1, 9 walls, 9 to go
+// Player 2: e9, 9 walls, 9 to go
+pub struct BoardDisplay<'a> {
+    board_state: &'a BoardState,
+    distance_player_index: Option<usize>,
+}
+
+impl fmt::Display for BoardState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let display = BoardDisplay {
+            board_state: self,
+            distance_player_index: None,
+        };
+        write!(f, "{}", display)
+    }
+}
+
+impl<'a> fmt::Display for BoardDisplay<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let board_state = self.board_state;
+        let is_wall = |x: isize, y: isize, orientation: WallOrientation| {
+            BoardState::is_wall_index_in_bounds(Vector2::new(x, y))
+                && board_state.get_wall(Vector2::new(x, y)) == Some(orientation)
+        };
+        let columns = "    a   b   c   d   e   f   g   h   i";
+        let border = format!("  +{}+", "-".repeat(35));
+
+        writeln!(f, "{}", columns)?;
+        writeln!(f, "{}", border)?;
+        for y in (0..9).rev() {
+            write!(f, "{} |", y + 1)?;
+            for x in 0..9 {
+                let cell = Vector2::new(x, y);
+                let content = if cell == board_state.get_player_position(0) {
+                    String::from(" 1 ")
+                } else if cell == board_state.get_player_position(1) {
+                    String::from(" 2 ")
+                } else if let Some(player_index) = self.distance_player_index {
+                    format!(
+                        "{:>2} ",
+                        board_state.distance_matrices[player_index][x as usize][y as usize]
+                    )
+                } else {
+                    String::from(" . ")
+                };
+                write!(f, "{}", content)?;
+                if x < 8 {
+                    let is_blocked =
+                        is_wall(x, y, WallOrientation::Vertical) || is_wall(x, y - 1, WallOrientation::Vertical);
+                    write!(f, "{}", if is_blocked { '|' } else { ' ' })?;
+                }
+            }
+            writeln!(f, "| {}", y + 1)?;
+
+            if y > 0 {
+                // The grid line between this row and the one below it.
+                write!(f, "  |")?;
+                for x in 0..9 {
+                    let is_blocked = is_wall(x, y - 1, WallOrientation::Horizontal)
+                        || is_wall(x - 1, y - 1, WallOrientation::Horizontal);
+                    write!(f, "{}", if is_blocked { "---" } else { "   " })?;
+                    if x < 8 {
+                        let joint = match board_state.get_wall(Vector2::new(x, y - 1)) {
+                            Some(WallOrientation::Horizontal) => '-',
+                            Some(WallOrientation::Vertical) => '|',
+                            None => ' ',
+                        };
+                        write!(f, "{}", joint)?;
+                    }
+                }
+                writeln!(f, "|")?;
+            }
+        }
+        writeln!(f, "{}", border)?;
+        writeln!(f, "{}", columns)?;
+        for i in 0..2 {
+            write!(
+                f,
+                "Player {}: {}, {} walls, {} to go",
+                i + 1,
+                format_cell(board_state.get_player_position(i)),
+                board_state.get_player_wall_count(i),
+                board_state.get_player_distance(i)
+            )?;
+            if i == 0 {
+                writeln!(f)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// Reads a board back from the diagram drawn by `Display`, ignoring indentation, blank lines and
+// anything after the wall count on the player lines. Distance overlays can't be read because
+// their numbers are indistinguishable from pawns. Wall owners aren't drawn so the walls are left
+// unowned.
+impl FromStr for BoardState {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        if !text.is_ascii() {
+            return Err(String::from("Diagram contains non-ASCII characters"));
+        }
+        let lines: Vec<&str> = text.lines().filter(|line| !line.trim().is_empty()).collect();
+        let indent = lines
+            .iter()
+            .map(|line| line.len() - line.trim_start().len())
+            .min()
+            .unwrap_or(0);
+        let lines: Vec<&str> = lines.iter().map(|line| line[indent..].trim_end()).collect();
+        if lines.len() != 23 {
+            return Err(format!("Expected 23 lines in the diagram but found {}", lines.len()));
+        }
+        // The grid is drawn from the top row down, with a grid line between each pair of rows.
+        let grid_char = |line: usize, column: usize| lines[line].as_bytes().get(column).copied().unwrap_or(b' ');
+        let cell_line = |y: isize| 2 + 2 * (8 - y as usize);
+
+        let mut board_state = BoardState::new();
+        let mut pawns = [Vec::new(), Vec::new()];
+        for y in 0..9 {
+            for x in 0..9 {
+                match grid_char(cell_line(y), 4 + 4 * x as usize) {
+                    b'1' => pawns[0].push(Vector2::new(x, y)),
+                    b'2' => pawns[1].push(Vector2::new(x, y)),
+                    b'.' => {}
+                    c => {
+                        let cell = format_cell(Vector2::new(x, y));
+                        return Err(format!("Invalid cell {}: {}", cell, c as char));
+                    }
+                }
+            }
+        }
+        for (i, cells) in pawns.iter().enumerate() {
+            if cells.len() != 1 {
+                return Err(format!(
+                    "Expected one pawn for player {} but found {}",
+                    i + 1,
+                    cells.len()
+                ));
+            }
+            board_state.set_player_position(i, cells[0]);
+        }
+
+        // Each wall is identified by the joint at its centre, with the rest of its segments checked
+        // by drawing the board again below.
+        for y in 0..8 {
+            for x in 0..8 {
+                let position = Vector2::new(x, y);
+                let orientation = match grid_char(cell_line(y) - 1, 6 + 4 * x as usize) {
+                    b'-' => WallOrientation::Horizontal,
+                    b'|' => WallOrientation::Vertical,
+                    b' ' => continue,
+                    c => {
+                        return Err(format!(
+                            "Invalid wall joint at {}: {}",
+                            format_cell(position),
+                            c as char
+                        ))
+                    }
+                };
+                if is_wall_overlapping(&board_state, position, orientation) {
+                    return Err(format!("Overlapping wall at {}", format_cell(position)));
+                }
+                board_state.set_wall(position, orientation);
+            }
+        }
+
+        for i in 0..2 {
+            let line = lines[21 + i];
+            let error = || format!("Invalid player line: {}", line);
+            let prefix = format!(
+                "Player {}: {}, ",
+                i + 1,
+                format_cell(board_state.get_player_position(i))
+            );
+            let wall_count = line
+                .strip_prefix(&prefix)
+                .and_then(|rest| rest.split(" walls").next())
+                .and_then(|count| count.parse::<usize>().ok())
+                .filter(|&count| count <= 10)
+                .ok_or_else(error)?;
+            board_state.set_player_wall_count(i, wall_count);
+        }
+
+        let drawn = board_state.to_string();
+        for (i, (line, expected)) in lines.iter().zip(drawn.lines()).take(21).enumerate() {
+            if line != &expected.trim_end() {
+                return Err(format!("Unexpected diagram line {}: {}", i + 1, line));
+            }
+        }
+
+        board_state.distance_matrices[0] = board_state.calculate_distance_matrix(8);
+        board_state.distance_matrices[1] = board_state.calculate_distance_matrix(0);
+        for i in 0..2 {
+            if board_state.get_player_distance(i) == -1 {
+                return Err(format!("Player {} can't reach their goal", i + 1));
+            }
+        }
+        let errors = board_state.check_invariants();
+        if !errors.is_empty() {
+            return Err(format!("Inconsistent diagram: {}", errors.join(", ")));
+        }
+        Ok(board_state)
+    }
+}
+
+#[cfg(test)]
+mod distance_tests;
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::validation::*;
+    use rand::rngs::StdRng;
+    use rand::seq::SliceRandom;
+    use rand::SeedableRng;
 
     #[test]
     fn set_wall() {
@@ -325,49 +778,245 @@ mod tests {
         assert_eq!(false, BoardState::is_cell_index_in_bounds(Vector2::new(4, 9)));
     }
 
+    #[test]
+    fn get_position_hash_depends_on_player_to_move() {
+        let board_state = BoardState::new();
+
+        assert_ne!(board_state.get_position_hash(0), board_state.get_position_hash(1));
+    }
+
+    #[test]
+    fn get_position_hash_ignores_move_order() {
+        let mut a = BoardState::new();
+        a.set_wall(Vector2::new(1, 1), WallOrientation::Vertical);
+        a.set_wall(Vector2::new(5, 5), WallOrientation::Horizontal);
+        let mut b = BoardState::new();
+        b.set_wall(Vector2::new(5, 5), WallOrientation::Horizontal);
+        b.set_wall(Vector2::new(1, 1), WallOrientation::Vertical);
+
+        assert_eq!(a.get_position_hash(0), b.get_position_hash(0));
+        b.set_player_position(0, Vector2::new(4, 1));
+        assert_ne!(a.get_position_hash(0), b.get_position_hash(0));
+    }
+
     #[test]
     fn get_player_distance() {
         let mut board_state = BoardState::new();
         board_state.set_wall(Vector2::new(5, 5), WallOrientation::Horizontal);
         board_state.set_player_position(0, Vector2::new(5, 5));
-        assert_eq!(4, board_state.get_player_distance(0));
+        assert_eq!(4, board_state.get_player_distance(0), "\n{}", board_state);
     }
 
+    // Walls that cut off the bottom left corner and force both players around them.
+    const CORNER_DIAGRAM: &str = "
+            a   b   c   d   e   f   g   h   i
+          +-----------------------------------+
+        9 | .   .   .   .   2   .   .   .   . | 9
+          |                                   |
+        8 | .   .   .   .   .   .   .   .   . | 8
+          |                                   |
+        7 | .   .   .   .   .   .   .   .   . | 7
+          |                                   |
+        6 | .   .   .   .   .   .   .   .   . | 6
+          |                                   |
+        5 | .   .   .   .   .   .   .   .   . | 5
+          |                                   |
+        4 | .   .   .   .   .   .   .   .   . | 4
+          |-------         -------            |
+        3 | .   . | .   .   .   . | .   .   . | 3
+          |       |               |           |
+        2 | .   . | .   .   .   . | .   .   . | 2
+          |------- ------- -------            |
+        1 | .   .   .   .   1   .   .   .   . | 1
+          +-----------------------------------+
+            a   b   c   d   e   f   g   h   i
+        Player 1: e1, 10 walls
+        Player 2: e9, 10 walls
+    ";
+
     #[test]
     fn get_distance_matrix_player_1() {
-        let mut board_state = BoardState::new();
-        board_state.set_wall(Vector2::new(0, 0), WallOrientation::Horizontal);
-        board_state.set_wall(Vector2::new(2, 0), WallOrientation::Horizontal);
-        board_state.set_wall(Vector2::new(4, 0), WallOrientation::Horizontal);
-        board_state.set_wall(Vector2::new(5, 1), WallOrientation::Vertical);
-        board_state.set_wall(Vector2::new(4, 2), WallOrientation::Horizontal);
-        board_state.set_wall(Vector2::new(1, 1), WallOrientation::Vertical);
-        board_state.set_wall(Vector2::new(0, 2), WallOrientation::Horizontal);
+        let board_state: BoardState = CORNER_DIAGRAM.parse().unwrap();
 
         let matrix = board_state.get_distance_matrix(0);
 
-        assert_eq!(0, matrix[4][8]);
-        assert_eq!(10, matrix[4][0]);
-        assert_eq!(8, matrix[4][1]);
-        assert_eq!(-1, matrix[1][1]);
+        assert_eq!(0, matrix[4][8], "\n{}", board_state.display_with_distances(0));
+        assert_eq!(10, matrix[4][0], "\n{}", board_state.display_with_distances(0));
+        assert_eq!(8, matrix[4][1], "\n{}", board_state.display_with_distances(0));
+        assert_eq!(-1, matrix[1][1], "\n{}", board_state.display_with_distances(0));
     }
 
     #[test]
     fn get_distance_matrix_player_2() {
-        let mut board_state = BoardState::new();
-        board_state.set_wall(Vector2::new(0, 0), WallOrientation::Horizontal);
-        board_state.set_wall(Vector2::new(2, 0), WallOrientation::Horizontal);
-        board_state.set_wall(Vector2::new(4, 0), WallOrientation::Horizontal);
-        board_state.set_wall(Vector2::new(5, 1), WallOrientation::Vertical);
-        board_state.set_wall(Vector2::new(4, 2), WallOrientation::Horizontal);
-        board_state.set_wall(Vector2::new(1, 1), WallOrientation::Vertical);
-        board_state.set_wall(Vector2::new(0, 2), WallOrientation::Horizontal);
+        let board_state: BoardState = CORNER_DIAGRAM.parse().unwrap();
 
         let matrix = board_state.get_distance_matrix(1);
 
-        assert_eq!(0, matrix[4][0]);
-        assert_eq!(10, matrix[4][8]);
-        assert_eq!(9, matrix[4][1]);
-        assert_eq!(-1, matrix[1][1]);
+        assert_eq!(0, matrix[4][0], "\n{}", board_state.display_with_distances(1));
+        assert_eq!(10, matrix[4][8], "\n{}", board_state.display_with_distances(1));
+        assert_eq!(9, matrix[4][1], "\n{}", board_state.display_with_distances(1));
+        assert_eq!(-1, matrix[1][1], "\n{}", board_state.display_with_distances(1));
+    }
+
+    #[test]
+    fn display_start() {
+        let expected = [
+            "    a   b   c   d   e   f   g   h   i",
+            "  +-----------------------------------+",
+            "9 | .   .   .   .   2   .   .   .   . | 9",
+            "  |                                   |",
+            "8 | .   .   .   .   .   .   .   .   . | 8",
+        ];
+        let text = BoardState::new().to_string();
+        let lines: Vec<&str> = text.lines().collect();
+
+        assert_eq!(expected, lines[..5]);
+        assert_eq!("Player 1: e1, 10 walls, 8 to go", lines[lines.len() - 2]);
+        assert_eq!("Player 2: e9, 10 walls, 8 to go", lines[lines.len() - 1]);
+    }
+
+    #[test]
+    fn display_walls() {
+        let mut board_state = BoardState::new();
+        board_state.set_wall(Vector2::new(3, 1), WallOrientation::Horizontal);
+        board_state.set_wall(Vector2::new(5, 0), WallOrientation::Vertical);
+
+        let text = board_state.to_string();
+        let lines: Vec<&str> = text.lines().collect();
+
+        assert_eq!("  |            -------                |", lines[15]);
+        assert_eq!("2 | .   .   .   .   .   . | .   .   . | 2", lines[16]);
+        assert_eq!("  |                       |           |", lines[17]);
+        assert_eq!("1 | .   .   .   .   1   . | .   .   . | 1", lines[18]);
+    }
+
+    #[test]
+    fn display_with_distances() {
+        let text = BoardState::new().display_with_distances(1).to_string();
+        let lines: Vec<&str> = text.lines().collect();
+
+        assert_eq!("9 | 8   8   8   8   2   8   8   8   8 | 9", lines[2]);
+        assert_eq!("1 | 0   0   0   0   1   0   0   0   0 | 1", lines[18]);
+    }
+
+    #[test]
+    fn parse_diagram_round_trip() {
+        let mut board_state = BoardState::new();
+        board_state.set_wall(Vector2::new(3, 1), WallOrientation::Horizontal);
+        board_state.set_wall(Vector2::new(5, 0), WallOrientation::Vertical);
+        board_state.set_player_position(1, Vector2::new(2, 6));
+        board_state.set_player_wall_count(0, 9);
+        board_state.set_player_wall_count(1, 3);
+
+        let parsed: BoardState = board_state.to_string().parse().unwrap();
+
+        assert_eq!(board_state.walls, parsed.walls);
+        assert_eq!(board_state.cell_connections, parsed.cell_connections);
+        assert_eq!(board_state.player_positions, parsed.player_positions);
+        assert_eq!(board_state.player_wall_counts, parsed.player_wall_counts);
+        assert_eq!(board_state.distance_matrices, parsed.distance_matrices);
+    }
+
+    #[test]
+    fn parse_diagram_invalid() {
+        let start = BoardState::new().to_string();
+        let parse = |text: String| text.parse::<BoardState>();
+
+        assert_eq!(
+            true,
+            parse(start.replace("Player 2: e9, 10 walls, 8 to go", "")).is_err()
+        );
+        assert_eq!(true, parse(start.replace(" 2 ", " . ")).is_err());
+        assert_eq!(true, parse(start.replacen(" . ", " 1 ", 1)).is_err());
+        assert_eq!(true, parse(start.replacen(" . ", " x ", 1)).is_err());
+        assert_eq!(true, parse(start.replace("10 walls", "11 walls")).is_err());
+        assert_eq!(true, parse(start.replace("e1, 10", "e2, 10")).is_err());
+        // A segment that isn't part of a wall.
+        assert_eq!(true, parse(start.replacen("  |    ", "  |--- ", 1)).is_err());
+        // Two walls sharing a segment.
+        assert_eq!(
+            true,
+            parse(start.replacen("  |           ", "  |----------- ", 1)).is_err()
+        );
+    }
+
+    #[test]
+    fn parse_diagram_rejects_trapped_player() {
+        let mut board_state = BoardState::new();
+        board_state.set_wall(Vector2::new(3, 0), WallOrientation::Horizontal);
+        board_state.set_wall(Vector2::new(2, 0), WallOrientation::Vertical);
+        board_state.set_wall(Vector2::new(4, 0), WallOrientation::Vertical);
+
+        let result = board_state.to_string().parse::<BoardState>();
+
+        assert_eq!(Err(String::from("Player 1 can't reach their goal")), result.map(|_| ()));
+    }
+
+    #[test]
+    fn check_invariants_start() {
+        assert_eq!(Vec::<String>::new(), BoardState::new().check_invariants());
+    }
+
+    #[test]
+    fn check_invariants_reports_each_mismatch() {
+        let mut board_state = BoardState::new();
+        board_state.walls[3][3] = Some(WallOrientation::Horizontal);
+        board_state.player_walls[5][5] = 1;
+        board_state.player_positions[1] = Vector2::new(4, 0);
+
+        let errors = board_state.check_invariants();
+
+        assert_eq!(false, errors.is_empty());
+        assert_eq!(
+            true,
+            errors.contains(&String::from("Connection d4 up is open but should be blocked"))
+        );
+        assert_eq!(
+            true,
+            errors.contains(&String::from("Connection e5 down is open but should be blocked"))
+        );
+        assert_eq!(
+            true,
+            errors.contains(&String::from("Distance from d4 for player 1 is 5 but should be 6"))
+        );
+        assert_eq!(
+            true,
+            errors.contains(&String::from("Empty wall position f6 has owner 1"))
+        );
+        assert_eq!(
+            true,
+            errors.contains(&String::from("Both players are on the same cell"))
+        );
+    }
+
+    #[test]
+    fn check_invariants_after_random_actions() {
+        let mut rng = StdRng::seed_from_u64(33);
+        for _ in 0..10 {
+            let mut board_state = BoardState::new();
+            let mut player_index = 0;
+            while board_state.get_player_distance(0) != 0 && board_state.get_player_distance(1) != 0 {
+                let mut actions = get_valid_move_actions(&board_state, player_index);
+                actions.append(&mut get_valid_block_actions(&board_state, player_index));
+                actions.shuffle(&mut rng);
+                let action = *actions
+                    .iter()
+                    .find(|action| validate_action(&board_state, player_index, action))
+                    .unwrap();
+                let mut new_board_state = board_state;
+                action.apply(&mut new_board_state, player_index);
+
+                assert_eq!(
+                    Vec::<String>::new(),
+                    new_board_state.check_invariants(),
+                    "\n{}\n{}\n{}",
+                    board_state,
+                    action,
+                    new_board_state
+                );
+                board_state = new_board_state;
+                player_index = 1 - player_index;
+            }
+        }
     }
 }
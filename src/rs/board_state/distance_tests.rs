@@ -0,0 +1,164 @@
+// Differential tests of the incremental distance updates in `update_distance_matrix`. Random games
+// are played and after every wall the stored matrices are compared with ones calculated from
+// scratch. A failing game is shrunk to the smallest sequence of walls that still fails, which
+// is much easier to debug than the game it came from.
+use super::*;
+use crate::notation::format_action;
+use crate::validation::{get_valid_move_positions, is_wall_overlapping};
+
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+
+const GAME_COUNT: u64 = 1000;
+
+type Wall = (Vector2<isize>, WallOrientation);
+
+// Returns the index of the first player whose stored matrix differs from a fresh calculation.
+fn find_mismatch(board_state: &BoardState) -> Option<usize> {
+    (0..2).find(|&i| board_state.distance_matrices[i] != board_state.calculate_distance_matrix(8 - i * 8))
+}
+
+// Places the walls in order on an empty board and returns the player affected by the first
+// mismatch, if any.
+fn replay_walls(walls: &[Wall]) -> Option<usize> {
+    let mut board_state = BoardState::new();
+    for &(position, orientation) in walls.iter() {
+        board_state.set_wall(position, orientation);
+        if let Some(player_index) = find_mismatch(&board_state) {
+            return Some(player_index);
+        }
+    }
+    None
+}
+
+// Removes walls one at a time for as long as the remaining sequence still fails.
+fn shrink(walls: &[Wall], fails: &dyn Fn(&[Wall]) -> bool) -> Vec<Wall> {
+    let mut walls = walls.to_vec();
+    let mut i = 0;
+    while i < walls.len() {
+        let mut candidate = walls.clone();
+        candidate.remove(i);
+        if fails(&candidate) {
+            walls = candidate;
+        } else {
+            i += 1;
+        }
+    }
+    walls
+}
+
+fn is_trapped(board_state: &BoardState) -> bool {
+    [8, 0].iter().enumerate().any(|(i, &row)| {
+        let position = board_state.get_player_position(i);
+        board_state.calculate_distance_matrix(row)[position.x as usize][position.y as usize] == -1
+    })
+}
+
+// Plays a random legal game until it ends or both players run out of walls, and returns the walls
+// placed up to and including the first one that caused a mismatch, if any.
+fn play_random_game(seed: u64) -> Option<Vec<Wall>> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut board_state = BoardState::new();
+    let mut walls = Vec::new();
+    let mut player_index = 0;
+    while board_state.get_player_distance(0) != 0 && board_state.get_player_distance(1) != 0 {
+        if board_state.get_player_wall_count(0) == 0 && board_state.get_player_wall_count(1) == 0 {
+            break;
+        }
+        if board_state.get_player_wall_count(player_index) > 0 && rng.gen_bool(0.5) {
+            // Legality is checked against fresh matrices so that it doesn't depend on the code
+            // under test.
+            let wall = loop {
+                let position = Vector2::new(rng.gen_range(0, 8), rng.gen_range(0, 8));
+                let orientation = *[WallOrientation::Horizontal, WallOrientation::Vertical]
+                    .choose(&mut rng)
+                    .unwrap();
+                if is_wall_overlapping(&board_state, position, orientation) {
+                    continue;
+                }
+                let mut new_board_state = board_state;
+                new_board_state.set_wall(position, orientation);
+                if !is_trapped(&new_board_state) {
+                    break (position, orientation);
+                }
+            };
+            // The wall is placed directly rather than through `Action::apply` so that a mismatch
+            // is reported here with its shrunk sequence instead of by the debug consistency check.
+            board_state.set_wall(wall.0, wall.1);
+            board_state.set_player_wall(wall.0, player_index);
+            board_state.set_player_wall_count(player_index, board_state.get_player_wall_count(player_index) - 1);
+            walls.push(wall);
+            if find_mismatch(&board_state).is_some() {
+                return Some(walls);
+            }
+        } else {
+            let moves = get_valid_move_positions(&board_state, player_index);
+            board_state.set_player_position(player_index, *moves.choose(&mut rng).unwrap());
+        }
+        player_index = 1 - player_index;
+    }
+    None
+}
+
+fn format_walls(walls: &[Wall]) -> String {
+    let names: Vec<String> = walls
+        .iter()
+        .map(|&(position, orientation)| format_action(&Action::Block(position, orientation)))
+        .collect();
+    names.join(" ")
+}
+
+#[test]
+fn incremental_distances_match_random_games() {
+    for seed in 0..GAME_COUNT {
+        if let Some(walls) = play_random_game(seed) {
+            let walls = shrink(&walls, &|walls| replay_walls(walls).is_some());
+            let player_index = replay_walls(&walls).unwrap();
+            let mut board_state = BoardState::new();
+            for &(position, orientation) in walls.iter() {
+                board_state.set_wall(position, orientation);
+            }
+            let mut expected = board_state;
+            expected.distance_matrices[player_index] = board_state.calculate_distance_matrix(8 - player_index * 8);
+            panic!(
+                "Distances for player {} differ in game {} after walls {}\nIncremental:\n{}\nExpected:\n{}",
+                player_index + 1,
+                seed,
+                format_walls(&walls),
+                board_state.display_with_distances(player_index),
+                expected.display_with_distances(player_index)
+            );
+        }
+    }
+}
+
+#[test]
+fn shrink_keeps_only_failing_walls() {
+    let walls: Vec<Wall> = (0..6)
+        .map(|i| (Vector2::new(i, i), WallOrientation::Horizontal))
+        .collect();
+    let fails = |walls: &[Wall]| {
+        walls.contains(&(Vector2::new(1, 1), WallOrientation::Horizontal))
+            && walls.contains(&(Vector2::new(4, 4), WallOrientation::Horizontal))
+    };
+
+    let shrunk = shrink(&walls, &fails);
+
+    assert_eq!("b2h e5h", format_walls(&shrunk));
+}
+
+#[test]
+fn replay_walls_matches_known_positions() {
+    let walls = [
+        (Vector2::new(0, 0), WallOrientation::Horizontal),
+        (Vector2::new(2, 0), WallOrientation::Horizontal),
+        (Vector2::new(4, 0), WallOrientation::Horizontal),
+        (Vector2::new(5, 1), WallOrientation::Vertical),
+        (Vector2::new(4, 2), WallOrientation::Horizontal),
+        (Vector2::new(1, 1), WallOrientation::Vertical),
+        (Vector2::new(0, 2), WallOrientation::Horizontal),
+    ];
+
+    assert_eq!(None, replay_walls(&walls));
+}
@@ -0,0 +1,317 @@
+use crate::action::Action;
+use crate::board_state::BoardState;
+use crate::validation::*;
+use crate::vector2::Vector2;
+use crate::wall_orientation::WallOrientation;
+
+use serde::Serialize;
+use std::cell::RefCell;
+use std::rc::Rc;
+use tsify::Tsify;
+
+const STATE_COUNT: usize = 81 * 81 * 2;
+
+type Walls = [[Option<WallOrientation>; 8]; 8];
+
+thread_local! {
+    // The table for the walls last solved with. The walls can't change once neither player has any
+    // left, so every position of the rest of the game is solved with the same table. Each thread
+    // keeps its own, since games played side by side have different walls.
+    static CACHED_TABLE: RefCell<Option<(Walls, Rc<EndgameTable>)>> = const { RefCell::new(None) };
+}
+
+// The game-theoretic result for the player to move, with the number of plies until the game ends
+// when both sides play perfectly. The winner takes the fastest win and the loser the longest loss.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Tsify)]
+pub enum EndgameResult {
+    Win(usize),
+    Loss(usize),
+    Draw,
+}
+
+pub struct EndgameSolution {
+    pub result: EndgameResult,
+    pub line: Vec<Action>,
+}
+
+pub struct EndgameSolver {}
+
+impl EndgameSolver {
+    // Once neither player has walls left the walls can no longer change, so the game is a race
+    // between the pawns that can be solved exactly.
+    pub fn can_solve(board_state: &BoardState) -> bool {
+        board_state.get_player_wall_count(0) == 0 && board_state.get_player_wall_count(1) == 0
+    }
+
+    pub fn solve(board_state: &BoardState, player_index: usize) -> Option<EndgameSolution> {
+        if !EndgameSolver::can_solve(board_state) {
+            return None;
+        }
+        let table = get_table(board_state);
+        let positions = board_state.player_positions;
+        let result = table.get_result(positions, player_index);
+        let line = table.get_line(positions, player_index);
+        Some(EndgameSolution { result, line })
+    }
+
+    pub fn take_action(board_state: &BoardState, player_index: usize) -> Option<Action> {
+        EndgameSolver::solve(board_state, player_index).and_then(|solution| solution.line.first().copied())
+    }
+}
+
+fn get_table(board_state: &BoardState) -> Rc<EndgameTable> {
+    CACHED_TABLE.with(|cache| {
+        let mut cache = cache.borrow_mut();
+        match &*cache {
+            Some((walls, table)) if *walls == board_state.walls => table.clone(),
+            _ => {
+                let table = Rc::new(EndgameTable::new(board_state));
+                *cache = Some((board_state.walls, table.clone()));
+                table
+            }
+        }
+    })
+}
+
+// The result of every combination of pawn positions and player to move for a fixed set of walls.
+struct EndgameTable {
+    results: Vec<Option<EndgameResult>>,
+    successors: Vec<Vec<usize>>,
+}
+
+impl EndgameTable {
+    fn new(board_state: &BoardState) -> Self {
+        let mut table = EndgameTable {
+            results: vec![None; STATE_COUNT],
+            successors: vec![Vec::new(); STATE_COUNT],
+        };
+
+        // Positions where a pawn already stands on its goal row are over.
+        let distance_matrices = [board_state.get_distance_matrix(0), board_state.get_distance_matrix(1)];
+        let is_at_goal = |player: usize, position: Vector2<isize>| {
+            distance_matrices[player][position.x as usize][position.y as usize] == 0
+        };
+        for state in 0..STATE_COUNT {
+            let (positions, player_index) = decode_state(state);
+            if positions[0] == positions[1] {
+                continue;
+            }
+            if is_at_goal(1 - player_index, positions[1 - player_index]) {
+                table.results[state] = Some(EndgameResult::Loss(0));
+            } else if is_at_goal(player_index, positions[player_index]) {
+                table.results[state] = Some(EndgameResult::Win(0));
+            } else {
+                let player_position = positions[player_index];
+                let opponent_position = positions[1 - player_index];
+                for position in get_accessible_cells(board_state, player_position, opponent_position) {
+                    let mut new_positions = positions;
+                    new_positions[player_index] = position;
+                    table.successors[state].push(encode_state(new_positions, 1 - player_index));
+                }
+            }
+        }
+
+        // Work backwards from the finished positions one ply at a time. On odd plies a position is
+        // won if any move reaches a position lost for the opponent one ply earlier. On even plies a
+        // position is lost once every move reaches a position won for the opponent. Positions that
+        // are never resolved can be prolonged forever by both players and are drawn.
+        let mut ply = 1;
+        loop {
+            let mut resolved = Vec::new();
+            for state in 0..STATE_COUNT {
+                if table.results[state].is_some() || table.successors[state].is_empty() {
+                    continue;
+                }
+                let children = table.successors[state].iter().map(|&child| table.results[child]);
+                let is_resolved = if ply % 2 == 1 {
+                    children
+                        .clone()
+                        .any(|result| result == Some(EndgameResult::Loss(ply - 1)))
+                } else {
+                    children
+                        .clone()
+                        .all(|result| matches!(result, Some(EndgameResult::Win(_))))
+                };
+                if is_resolved {
+                    resolved.push(state);
+                }
+            }
+            if resolved.is_empty() {
+                break;
+            }
+            for state in resolved {
+                table.results[state] = Some(if ply % 2 == 1 {
+                    EndgameResult::Win(ply)
+                } else {
+                    EndgameResult::Loss(ply)
+                });
+            }
+            ply += 1;
+        }
+        table
+    }
+
+    fn get_result(&self, positions: [Vector2<isize>; 2], player_index: usize) -> EndgameResult {
+        self.results[encode_state(positions, player_index)].unwrap_or(EndgameResult::Draw)
+    }
+
+    fn get_best_successor(&self, state: usize) -> Option<usize> {
+        let result = self.results[state].unwrap_or(EndgameResult::Draw);
+        let target = match result {
+            EndgameResult::Win(0) | EndgameResult::Loss(0) => return None,
+            EndgameResult::Win(plies) => Some(EndgameResult::Loss(plies - 1)),
+            EndgameResult::Loss(plies) => Some(EndgameResult::Win(plies - 1)),
+            EndgameResult::Draw => None,
+        };
+        self.successors[state]
+            .iter()
+            .copied()
+            .find(|&child| self.results[child] == target)
+    }
+
+    // The moves both players make with perfect play. A drawn position has no line, since neither
+    // player can force anything from it.
+    fn get_line(&self, positions: [Vector2<isize>; 2], player_index: usize) -> Vec<Action> {
+        let mut line = Vec::new();
+        let mut state = encode_state(positions, player_index);
+        while let Some(child) = self.get_best_successor(state) {
+            let (positions, _) = decode_state(child);
+            let (_, player_index) = decode_state(state);
+            line.push(Action::Move(positions[player_index]));
+            state = child;
+        }
+        line
+    }
+}
+
+fn encode_state(positions: [Vector2<isize>; 2], player_index: usize) -> usize {
+    let cell_0 = (positions[0].x * 9 + positions[0].y) as usize;
+    let cell_1 = (positions[1].x * 9 + positions[1].y) as usize;
+    (cell_0 * 81 + cell_1) * 2 + player_index
+}
+
+fn decode_state(state: usize) -> ([Vector2<isize>; 2], usize) {
+    let player_index = state % 2;
+    let cell_1 = (state / 2 % 81) as isize;
+    let cell_0 = (state / 2 / 81) as isize;
+    let positions = [
+        Vector2::new(cell_0 / 9, cell_0 % 9),
+        Vector2::new(cell_1 / 9, cell_1 % 9),
+    ];
+    (positions, player_index)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wall_orientation::WallOrientation;
+
+    fn new_endgame_board() -> BoardState {
+        let mut board_state = BoardState::new();
+        board_state.set_player_wall_count(0, 0);
+        board_state.set_player_wall_count(1, 0);
+        board_state
+    }
+
+    #[test]
+    fn solve_requires_both_players_out_of_walls() {
+        let mut board_state = BoardState::new();
+        board_state.set_player_wall_count(0, 0);
+
+        assert_eq!(true, EndgameSolver::solve(&board_state, 0).is_none());
+    }
+
+    #[test]
+    fn solve_win_in_one() {
+        let mut board_state = new_endgame_board();
+        board_state.set_player_position(0, Vector2::new(2, 7));
+
+        let solution = EndgameSolver::solve(&board_state, 0).unwrap();
+
+        assert_eq!(EndgameResult::Win(1), solution.result);
+        assert_eq!(vec![Action::Move(Vector2::new(2, 8))], solution.line);
+    }
+
+    #[test]
+    fn solve_win_in_one_by_jumping() {
+        let mut board_state = new_endgame_board();
+        board_state.set_player_position(0, Vector2::new(4, 6));
+        board_state.set_player_position(1, Vector2::new(4, 7));
+
+        let solution = EndgameSolver::solve(&board_state, 0).unwrap();
+
+        assert_eq!(EndgameResult::Win(1), solution.result);
+        assert_eq!(vec![Action::Move(Vector2::new(4, 8))], solution.line);
+    }
+
+    #[test]
+    fn solve_loss_takes_longest_line() {
+        let mut board_state = new_endgame_board();
+        board_state.set_player_position(0, Vector2::new(0, 0));
+        board_state.set_player_position(1, Vector2::new(8, 1));
+
+        let solution = EndgameSolver::solve(&board_state, 0).unwrap();
+
+        assert_eq!(EndgameResult::Loss(2), solution.result);
+        assert_eq!(2, solution.line.len());
+    }
+
+    #[test]
+    fn solve_race_decided_by_walls() {
+        // Without the walls player 1 would reach their goal first.
+        let mut board_state = new_endgame_board();
+        board_state.set_wall(Vector2::new(0, 0), WallOrientation::Horizontal);
+        board_state.set_wall(Vector2::new(1, 0), WallOrientation::Vertical);
+        board_state.set_player_position(0, Vector2::new(8, 5));
+        board_state.set_player_position(1, Vector2::new(0, 2));
+
+        let solution = EndgameSolver::solve(&board_state, 0).unwrap();
+
+        assert_eq!(EndgameResult::Win(5), solution.result);
+    }
+
+    #[test]
+    fn solve_line_is_valid() {
+        let board_state = new_endgame_board();
+
+        let solution = EndgameSolver::solve(&board_state, 0).unwrap();
+
+        let mut board_state = board_state;
+        let mut player_index = 0;
+        for action in solution.line.iter() {
+            assert_eq!(true, validate_action(&board_state, player_index, action));
+            action.apply(&mut board_state, player_index);
+            player_index = 1 - player_index;
+        }
+        assert_eq!(
+            true,
+            matches!(solution.result, EndgameResult::Win(_) | EndgameResult::Loss(_))
+        );
+        assert_eq!(
+            true,
+            board_state.get_player_distance(0) == 0 || board_state.get_player_distance(1) == 0
+        );
+    }
+
+    #[test]
+    fn table_is_reused_while_walls_stay_the_same() {
+        let mut board_state = new_endgame_board();
+        let table = get_table(&board_state);
+        board_state.set_player_position(0, Vector2::new(2, 7));
+
+        assert_eq!(true, Rc::ptr_eq(&table, &get_table(&board_state)));
+
+        board_state.set_wall(Vector2::new(2, 7), WallOrientation::Horizontal);
+        let solution = EndgameSolver::solve(&board_state, 0).unwrap();
+
+        assert_eq!(false, Rc::ptr_eq(&table, &get_table(&board_state)));
+        assert_eq!(EndgameResult::Win(3), solution.result);
+    }
+
+    #[test]
+    fn encode_state_round_trip() {
+        let positions = [Vector2::new(3, 8), Vector2::new(7, 2)];
+
+        assert_eq!((positions, 1), decode_state(encode_state(positions, 1)));
+    }
+}
@@ -0,0 +1,533 @@
+use crate::action::Action;
+use crate::board_state::BoardState;
+use crate::endgame_solver::{EndgameResult, EndgameSolver};
+use crate::iterative_search::{SearchInfo, SearchLimits};
+use crate::minimax_player::{format_score, MinimaxPlayer};
+use crate::notation::*;
+use crate::opening_book::OpeningBook;
+#[cfg(feature = "parallel")]
+use crate::parallel_search::ParallelMinimaxPlayer;
+use crate::player::check_can_act;
+use crate::random_player::RandomPlayer;
+use crate::shortest_path_player::ShortestPathPlayer;
+use crate::validation::*;
+
+use std::fs;
+use std::io::{self, BufRead};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Sender};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+// The maximum depth searched by "go infinite", which in practice runs until "stop" is received.
+const MAX_SEARCH_DEPTH: usize = 64;
+
+// A line based protocol modelled on UCI that lets other programs drive the engine. Each command
+// is a single line on stdin and each reply a single line on stdout.
+//
+//   qei                                   -> id lines, option lines, qeiok
+//   isready                               -> readyok
+//   setoption name <name> value <value>
+//   newgame
+//   position (startpos | <position string>) [moves <action> ...]
+//   go [depth <plies>] [movetime <ms>] [infinite]
+//                                         -> info depth <d> score <s> nodes <n> pv <action> ...
+//                                         -> bestmove <action>, or info string <error> when there is
+//                                            no action to take
+//   stop
+//   quit
+//
+// Scores are from the side of the engine's player. They are the difference between the players'
+// distances to their goals, or "win <plies>" or "loss <plies>" once the result is forced. Actions
+// and positions use the notation in `notation.rs`.
+pub fn run() {
+    let (sender, receiver) = mpsc::channel::<String>();
+    let printer = thread::spawn(move || {
+        for line in receiver {
+            println!("{}", line);
+        }
+    });
+
+    let mut engine = Engine::new(sender);
+    for line in io::stdin().lock().lines() {
+        match line {
+            Ok(line) => {
+                if !engine.handle_command(&line) {
+                    break;
+                }
+            }
+            Err(_) => break,
+        }
+    }
+    engine.stop_search();
+    drop(engine);
+    printer.join().unwrap();
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum PlayerType {
+    Minimax,
+    ShortestPath,
+    Random,
+}
+
+#[derive(Clone)]
+struct EngineOptions {
+    player_type: PlayerType,
+    depth: usize,
+    move_chance: f32,
+    opening_book: Option<Arc<OpeningBook>>,
+    thread_count: usize,
+}
+
+pub struct Engine {
+    output: Sender<String>,
+    options: EngineOptions,
+    board_state: BoardState,
+    player_index: usize,
+    search: Option<(JoinHandle<()>, Arc<AtomicBool>)>,
+}
+
+impl Engine {
+    pub fn new(output: Sender<String>) -> Self {
+        Engine {
+            output,
+            options: EngineOptions {
+                player_type: PlayerType::Minimax,
+                depth: 3,
+                move_chance: 0.5,
+                opening_book: None,
+                thread_count: 1,
+            },
+            board_state: BoardState::new(),
+            player_index: 0,
+            search: None,
+        }
+    }
+
+    // Returns false once the engine should quit.
+    pub fn handle_command(&mut self, line: &str) -> bool {
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        match tokens.first() {
+            Some(&"qei") => {
+                self.send("id name quoridor-rust");
+                self.send("id author Zack Hill, Mark Tilton");
+                self.send("option name Player type combo default minimax var minimax var shortest-path var random");
+                self.send("option name Depth type spin default 3 min 1 max 64");
+                self.send("option name MoveChance type string default 0.5");
+                self.send("option name BookFile type string default <empty>");
+                if cfg!(feature = "parallel") {
+                    self.send("option name Threads type spin default 1 min 1 max 64");
+                }
+                self.send("qeiok");
+            }
+            Some(&"isready") => self.send("readyok"),
+            Some(&"setoption") => self.set_option(&tokens[1..]),
+            Some(&"newgame") => {
+                self.stop_search();
+                self.board_state = BoardState::new();
+                self.player_index = 0;
+            }
+            Some(&"position") => self.set_position(&tokens[1..]),
+            Some(&"go") => self.go(&tokens[1..]),
+            Some(&"stop") => self.stop_search(),
+            Some(&"quit") => return false,
+            Some(command) => self.send(&format!("info string Unknown command: {}", command)),
+            None => {}
+        }
+        true
+    }
+
+    fn send(&self, line: &str) {
+        // The receiver only goes away when the program is shutting down.
+        let _ = self.output.send(String::from(line));
+    }
+
+    fn set_option(&mut self, tokens: &[&str]) {
+        let value_index = tokens.iter().position(|&token| token == "value");
+        let (name, value) = match (tokens.first(), value_index) {
+            (Some(&"name"), Some(index)) => (tokens[1..index].join(" "), tokens[index + 1..].join(" ")),
+            _ => {
+                self.send("info string Expected: setoption name <name> value <value>");
+                return;
+            }
+        };
+        match name.to_lowercase().as_str() {
+            "player" => match value.as_str() {
+                "minimax" => self.options.player_type = PlayerType::Minimax,
+                "shortest-path" => self.options.player_type = PlayerType::ShortestPath,
+                "random" => self.options.player_type = PlayerType::Random,
+                _ => self.send(&format!("info string Unknown player: {}", value)),
+            },
+            "depth" => match value.parse::<usize>() {
+                Ok(depth) if depth > 0 => self.options.depth = depth,
+                _ => self.send(&format!("info string Invalid depth: {}", value)),
+            },
+            "movechance" => match value.parse::<f32>() {
+                Ok(move_chance) => self.options.move_chance = move_chance,
+                Err(_) => self.send(&format!("info string Invalid move chance: {}", value)),
+            },
+            "bookfile" => {
+                if value.is_empty() || value == "<empty>" {
+                    self.options.opening_book = None;
+                    return;
+                }
+                let result = fs::read(&value)
+                    .map_err(|error| error.to_string())
+                    .and_then(|bytes| OpeningBook::from_bytes(&bytes));
+                match result {
+                    Ok(book) => self.options.opening_book = Some(Arc::new(book)),
+                    Err(error) => self.send(&format!("info string Failed to load book: {}", error)),
+                }
+            }
+            "threads" if cfg!(feature = "parallel") => match value.parse::<usize>() {
+                Ok(thread_count) if thread_count > 0 => self.options.thread_count = thread_count,
+                _ => self.send(&format!("info string Invalid thread count: {}", value)),
+            },
+            _ => self.send(&format!("info string Unknown option: {}", name)),
+        }
+    }
+
+    fn set_position(&mut self, tokens: &[&str]) {
+        self.stop_search();
+        let (mut board_state, mut player_index) = match tokens.first() {
+            Some(&"startpos") => (BoardState::new(), 0),
+            Some(text) => match parse_position(text) {
+                Ok(position) => position,
+                Err(error) => {
+                    self.send(&format!("info string {}", error));
+                    return;
+                }
+            },
+            None => {
+                self.send("info string Expected: position (startpos | <position>) [moves ...]");
+                return;
+            }
+        };
+        if tokens.get(1) == Some(&"moves") {
+            for text in tokens[2..].iter() {
+                let action = match parse_action(text) {
+                    Ok(action) if validate_action(&board_state, player_index, &action) => action,
+                    _ => {
+                        self.send(&format!("info string Illegal action: {}", text));
+                        return;
+                    }
+                };
+                action.apply(&mut board_state, player_index);
+                player_index = 1 - player_index;
+            }
+        }
+        self.board_state = board_state;
+        self.player_index = player_index;
+    }
+
+    fn go(&mut self, tokens: &[&str]) {
+        self.stop_search();
+        if let Err(error) = check_can_act(&self.board_state, self.player_index) {
+            self.send(&format!("info string {}", error));
+            return;
+        }
+
+        let mut limits = SearchLimits {
+            max_depth: self.options.depth,
+            time_limit: None,
+            stop: None,
+        };
+        let mut i = 0;
+        while i < tokens.len() {
+            let value = tokens.get(i + 1).and_then(|value| value.parse::<u64>().ok());
+            match (tokens[i], value) {
+                ("depth", Some(depth)) => {
+                    limits.max_depth = depth as usize;
+                    i += 1;
+                }
+                ("movetime", Some(milliseconds)) => {
+                    limits.time_limit = Some(Duration::from_millis(milliseconds));
+                    limits.max_depth = MAX_SEARCH_DEPTH;
+                    i += 1;
+                }
+                ("infinite", _) => limits.max_depth = MAX_SEARCH_DEPTH,
+                _ => self.send(&format!("info string Ignoring go parameter: {}", tokens[i])),
+            }
+            i += 1;
+        }
+
+        let stop = Arc::new(AtomicBool::new(false));
+        limits.stop = Some(stop.clone());
+        let output = self.output.clone();
+        let options = self.options.clone();
+        let board_state = self.board_state;
+        let player_index = self.player_index;
+        let handle = thread::spawn(move || {
+            let reply = match choose_action(&board_state, player_index, &options, &limits, &output) {
+                Ok(action) => format!("bestmove {}", format_action(&action)),
+                Err(error) => format!("info string {}", error),
+            };
+            let _ = output.send(reply);
+        });
+        self.search = Some((handle, stop));
+    }
+
+    pub fn stop_search(&mut self) {
+        if let Some((handle, stop)) = self.search.take() {
+            stop.store(true, Ordering::Relaxed);
+            if handle.join().is_err() {
+                self.send("info string The search failed");
+            }
+        }
+    }
+}
+
+fn choose_action(
+    board_state: &BoardState,
+    player_index: usize,
+    options: &EngineOptions,
+    limits: &SearchLimits,
+    output: &Sender<String>,
+) -> Result<Action, String> {
+    if let Some(action) = options
+        .opening_book
+        .as_ref()
+        .and_then(|book| book.probe(board_state, player_index))
+    {
+        let _ = output.send(String::from("info string book"));
+        return Ok(action);
+    }
+
+    if let Some(solution) = EndgameSolver::solve(board_state, player_index) {
+        if let Some(&action) = solution.line.first() {
+            let score = match solution.result {
+                EndgameResult::Win(plies) => format!("win {}", plies),
+                EndgameResult::Loss(plies) => format!("loss {}", plies),
+                EndgameResult::Draw => String::from("draw"),
+            };
+            let _ = output.send(format!(
+                "info depth {} score {} nodes 0 pv {}",
+                solution.line.len(),
+                score,
+                format_line(&solution.line)
+            ));
+            return Ok(action);
+        }
+    }
+
+    match options.player_type {
+        PlayerType::Minimax => {
+            let mut on_iteration = |info: &SearchInfo| {
+                let _ = output.send(format!(
+                    "info depth {} score {} nodes {} pv {}",
+                    info.depth,
+                    format_score(info.score),
+                    info.nodes,
+                    format_line(&info.principal_variation)
+                ));
+            };
+            let info = search(
+                board_state,
+                player_index,
+                limits,
+                options.thread_count,
+                &mut on_iteration,
+            );
+            info.principal_variation
+                .first()
+                .copied()
+                .ok_or_else(|| String::from("The search found no action"))
+        }
+        PlayerType::ShortestPath => Ok(ShortestPathPlayer::take_action(
+            board_state,
+            player_index,
+            options.move_chance,
+        )),
+        PlayerType::Random => Ok(RandomPlayer::take_action(
+            board_state,
+            player_index,
+            options.move_chance,
+        )),
+    }
+}
+
+#[cfg(feature = "parallel")]
+fn search(
+    board_state: &BoardState,
+    player_index: usize,
+    limits: &SearchLimits,
+    thread_count: usize,
+    on_iteration: &mut dyn FnMut(&SearchInfo),
+) -> SearchInfo {
+    if thread_count > 1 {
+        ParallelMinimaxPlayer::search(board_state, player_index, limits, thread_count, on_iteration)
+    } else {
+        MinimaxPlayer::search(board_state, player_index, limits, on_iteration)
+    }
+}
+
+// Without the parallel feature the Threads option is never offered, so the count is always 1.
+#[cfg(not(feature = "parallel"))]
+fn search(
+    board_state: &BoardState,
+    player_index: usize,
+    limits: &SearchLimits,
+    _thread_count: usize,
+    on_iteration: &mut dyn FnMut(&SearchInfo),
+) -> SearchInfo {
+    MinimaxPlayer::search(board_state, player_index, limits, on_iteration)
+}
+
+fn format_line(actions: &[Action]) -> String {
+    actions.iter().map(format_action).collect::<Vec<String>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc::Receiver;
+
+    fn new_engine() -> (Engine, Receiver<String>) {
+        let (sender, receiver) = mpsc::channel();
+        (Engine::new(sender), receiver)
+    }
+
+    fn read_until(receiver: &Receiver<String>, prefix: &str) -> Vec<String> {
+        let mut lines = Vec::new();
+        loop {
+            let line = receiver.recv_timeout(Duration::from_secs(30)).unwrap();
+            let is_done = line.starts_with(prefix);
+            lines.push(line);
+            if is_done {
+                return lines;
+            }
+        }
+    }
+
+    #[test]
+    fn handshake() {
+        let (mut engine, receiver) = new_engine();
+
+        engine.handle_command("qei");
+        engine.handle_command("isready");
+
+        let lines = read_until(&receiver, "readyok");
+        assert_eq!("id name quoridor-rust", lines[0]);
+        assert_eq!(true, lines.contains(&String::from("qeiok")));
+    }
+
+    #[test]
+    fn position_with_moves() {
+        let (mut engine, _receiver) = new_engine();
+
+        engine.handle_command("position startpos moves e2 e8 d4h");
+
+        assert_eq!(
+            "d4h.e2e8.9.10.2",
+            format_position(&engine.board_state, engine.player_index)
+        );
+    }
+
+    #[test]
+    fn position_from_string() {
+        let (mut engine, _receiver) = new_engine();
+
+        engine.handle_command("position d4h.e2e8.9.10.2 moves e7");
+
+        assert_eq!(
+            "d4h.e2e7.9.10.1",
+            format_position(&engine.board_state, engine.player_index)
+        );
+    }
+
+    #[test]
+    fn position_rejects_illegal_moves() {
+        let (mut engine, receiver) = new_engine();
+
+        engine.handle_command("position startpos moves e3");
+
+        assert_eq!("info string Illegal action: e3", receiver.recv().unwrap());
+        assert_eq!(
+            "-.e1e9.10.10.1",
+            format_position(&engine.board_state, engine.player_index)
+        );
+    }
+
+    #[test]
+    fn go_depth_reports_info_and_best_move() {
+        let (mut engine, receiver) = new_engine();
+
+        engine.handle_command("position startpos");
+        engine.handle_command("go depth 2");
+
+        let lines = read_until(&receiver, "bestmove");
+        assert_eq!(true, lines[0].starts_with("info depth 1 score "));
+        assert_eq!(true, lines[1].starts_with("info depth 2 score "));
+        let best_move = lines[2].split_whitespace().nth(1).unwrap();
+        let pv_move = lines[1].split_whitespace().skip_while(|&t| t != "pv").nth(1).unwrap();
+        assert_eq!(pv_move, best_move);
+    }
+
+    #[test]
+    fn go_infinite_until_stop() {
+        let (mut engine, receiver) = new_engine();
+
+        engine.handle_command("go infinite");
+        thread::sleep(Duration::from_millis(50));
+        engine.handle_command("stop");
+
+        let lines = read_until(&receiver, "bestmove");
+        let best_move = lines.last().unwrap().split_whitespace().nth(1).unwrap();
+        assert_eq!(
+            true,
+            validate_action(&BoardState::new(), 0, &parse_action(best_move).unwrap())
+        );
+    }
+
+    #[test]
+    fn go_uses_endgame_solver() {
+        let (mut engine, receiver) = new_engine();
+
+        engine.handle_command("position -.e8a5.0.0.1");
+        engine.handle_command("go depth 1");
+
+        let lines = read_until(&receiver, "bestmove");
+        assert_eq!("info depth 1 score win 1 nodes 0 pv e9", lines[0]);
+        assert_eq!("bestmove e9", lines[1]);
+    }
+
+    #[test]
+    fn go_reports_game_over() {
+        let (mut engine, receiver) = new_engine();
+
+        engine.handle_command("position -.e8a5.0.0.1 moves e9");
+        engine.handle_command("go depth 1");
+        engine.handle_command("isready");
+
+        assert_eq!(
+            vec![String::from("info string The game is over"), String::from("readyok")],
+            read_until(&receiver, "readyok")
+        );
+    }
+
+    #[test]
+    fn set_option_player() {
+        let (mut engine, _receiver) = new_engine();
+
+        engine.handle_command("setoption name Player value shortest-path");
+        engine.handle_command("setoption name Depth value 5");
+
+        assert_eq!(PlayerType::ShortestPath, engine.options.player_type);
+        assert_eq!(5, engine.options.depth);
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn go_with_threads() {
+        let (mut engine, receiver) = new_engine();
+
+        engine.handle_command("setoption name Threads value 2");
+        engine.handle_command("go depth 2");
+        let lines = read_until(&receiver, "bestmove");
+
+        assert_eq!(2, engine.options.thread_count);
+        assert_eq!(true, lines[1].starts_with("info depth 2 "));
+    }
+}
@@ -0,0 +1,228 @@
+use crate::action::Action;
+use crate::board_state::BoardState;
+use crate::notation::*;
+use crate::player::Player;
+use crate::validation::*;
+
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// A player backed by another program that speaks the engine protocol described in `engine.rs`.
+// The program is given the full position before every move, so it doesn't need to track the
+// game itself.
+pub struct ExternalPlayer {
+    name: String,
+    child: Child,
+    stdin: ChildStdin,
+    lines: Receiver<String>,
+    timeout: Duration,
+    // Set when a search was stopped, since its reply may still be on its way.
+    is_out_of_sync: bool,
+}
+
+impl ExternalPlayer {
+    // Starts the engine and waits for it to finish the handshake. `timeout` applies to the
+    // handshake and to each move.
+    pub fn new(command: &[String], timeout: Duration) -> Result<Self, String> {
+        let (program, args) = command.split_first().ok_or("No engine command given")?;
+        let mut child = Command::new(program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|error| format!("Failed to start {}: {}", program, error))?;
+        let stdin = child.stdin.take().unwrap();
+        let stdout = child.stdout.take().unwrap();
+
+        // Output is read on its own thread so that waiting on it can time out.
+        let (sender, lines) = mpsc::channel();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                match line {
+                    Ok(line) => {
+                        if sender.send(line).is_err() {
+                            break;
+                        }
+                    }
+                    Err(_) => break,
+                }
+            }
+        });
+
+        let mut player = ExternalPlayer {
+            name: program.clone(),
+            child,
+            stdin,
+            lines,
+            timeout,
+            is_out_of_sync: false,
+        };
+        player.send("qei")?;
+        let deadline = Instant::now() + timeout;
+        loop {
+            let line = player.receive(deadline)?;
+            if let Some(name) = line.strip_prefix("id name ") {
+                player.name = String::from(name);
+            } else if line == "qeiok" {
+                break;
+            }
+        }
+        Ok(player)
+    }
+
+    fn send(&mut self, line: &str) -> Result<(), String> {
+        writeln!(self.stdin, "{}", line)
+            .and_then(|_| self.stdin.flush())
+            .map_err(|error| format!("Failed to write to {}: {}", self.name, error))
+    }
+
+    fn receive(&self, deadline: Instant) -> Result<String, String> {
+        let timeout = deadline.saturating_duration_since(Instant::now());
+        match self.lines.recv_timeout(timeout) {
+            Ok(line) => Ok(line),
+            Err(RecvTimeoutError::Timeout) => Err(format!("{} timed out", self.name)),
+            Err(RecvTimeoutError::Disconnected) => Err(format!("{} exited", self.name)),
+        }
+    }
+    // Waits until the engine has handled everything sent to it so far, discarding what it sends
+    // before then.
+    fn synchronize(&mut self) -> Result<(), String> {
+        self.send("isready")?;
+        let deadline = Instant::now() + self.timeout;
+        while self.receive(deadline)? != "readyok" {}
+        self.is_out_of_sync = false;
+        Ok(())
+    }
+}
+
+impl Player for ExternalPlayer {
+    fn get_name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn choose_action(&mut self, board_state: &BoardState, player_index: usize) -> Result<Action, String> {
+        // The reply to a search that timed out can arrive at any time after it was stopped.
+        if self.is_out_of_sync {
+            self.synchronize()?;
+        }
+
+        self.send(&format!("position {}", format_position(board_state, player_index)))?;
+        self.send(&format!("go movetime {}", self.timeout.as_millis()))?;
+
+        // Allow a little longer than the move time for the reply to arrive.
+        let deadline = Instant::now() + self.timeout + self.timeout / 10;
+        let best_move = loop {
+            match self.receive(deadline) {
+                Ok(line) => {
+                    if let Some(best_move) = line.strip_prefix("bestmove ") {
+                        break String::from(best_move.trim());
+                    }
+                }
+                Err(error) => {
+                    let _ = self.send("stop");
+                    self.is_out_of_sync = true;
+                    return Err(error);
+                }
+            }
+        };
+
+        let action = parse_action(&best_move).map_err(|error| format!("{} sent {}", self.name, error))?;
+        if !validate_action(board_state, player_index, &action) {
+            return Err(format!("{} sent illegal action {}", self.name, best_move));
+        }
+        Ok(action)
+    }
+}
+
+impl Drop for ExternalPlayer {
+    fn drop(&mut self) {
+        let _ = self.send("quit");
+        // Give the engine a moment to exit on its own before killing it.
+        for _ in 0..10 {
+            if let Ok(Some(_)) = self.child.try_wait() {
+                return;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vector2::Vector2;
+
+    fn stand_in_engine(mode: &str) -> Result<ExternalPlayer, String> {
+        let script = format!("{}/tests/stand_in_engine.sh", env!("CARGO_MANIFEST_DIR"));
+        let command = vec![String::from("sh"), script, String::from(mode)];
+        ExternalPlayer::new(&command, Duration::from_millis(500))
+    }
+
+    #[test]
+    fn handshake_reads_name() {
+        let player = stand_in_engine("forward").unwrap();
+
+        assert_eq!("stand-in", player.get_name());
+    }
+
+    #[test]
+    fn choose_action_for_each_player() {
+        let mut player = stand_in_engine("forward").unwrap();
+        let board_state = BoardState::new();
+
+        assert_eq!(
+            Ok(Action::Move(Vector2::new(4, 1))),
+            player.choose_action(&board_state, 0)
+        );
+        assert_eq!(
+            Ok(Action::Move(Vector2::new(4, 7))),
+            player.choose_action(&board_state, 1)
+        );
+    }
+
+    #[test]
+    fn choose_action_times_out() {
+        let mut player = stand_in_engine("hang").unwrap();
+
+        let result = player.choose_action(&BoardState::new(), 0);
+
+        assert_eq!(Err(String::from("stand-in timed out")), result);
+    }
+
+    #[test]
+    fn choose_action_ignores_reply_to_stopped_search() {
+        let mut player = stand_in_engine("late").unwrap();
+        let board_state = BoardState::new();
+
+        assert_eq!(
+            Err(String::from("stand-in timed out")),
+            player.choose_action(&board_state, 0)
+        );
+        assert_eq!(
+            Ok(Action::Move(Vector2::new(4, 1))),
+            player.choose_action(&board_state, 0)
+        );
+    }
+
+    #[test]
+    fn choose_action_rejects_illegal_actions() {
+        let mut player = stand_in_engine("illegal").unwrap();
+
+        let result = player.choose_action(&BoardState::new(), 0);
+
+        assert_eq!(Err(String::from("stand-in sent illegal action a1")), result);
+    }
+
+    #[test]
+    fn new_fails_for_missing_program() {
+        let command = vec![String::from("./no-such-engine")];
+
+        assert_eq!(true, ExternalPlayer::new(&command, Duration::from_millis(500)).is_err());
+    }
+}
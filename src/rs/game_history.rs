@@ -0,0 +1,184 @@
+use crate::board_state::BoardState;
+
+use serde::Serialize;
+use std::collections::HashMap;
+use tsify::Tsify;
+
+pub const DEFAULT_REPETITION_COUNT: usize = 3;
+pub const DEFAULT_MAX_PLY_COUNT: usize = 200;
+
+// How a game ended. A win holds the index of the winning player.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Tsify)]
+pub enum GameResult {
+    Win(usize),
+    Draw,
+}
+
+// Bots that only move their pawns can shuffle back and forth forever, so a game is drawn once the
+// same position comes up `repetition_count` times or after `max_ply_count` actions. A count of 0
+// turns that rule off. A repetition count of 1 isn't allowed, since the position a game starts
+// from has already come up once.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct DrawRules {
+    pub repetition_count: usize,
+    pub max_ply_count: usize,
+}
+
+impl DrawRules {
+    pub fn new(repetition_count: usize, max_ply_count: usize) -> Result<Self, String> {
+        if repetition_count == 1 {
+            return Err(String::from("The repetition count must be 0 or at least 2"));
+        }
+        Ok(DrawRules {
+            repetition_count,
+            max_ply_count,
+        })
+    }
+}
+
+impl Default for DrawRules {
+    fn default() -> Self {
+        DrawRules {
+            repetition_count: DEFAULT_REPETITION_COUNT,
+            max_ply_count: DEFAULT_MAX_PLY_COUNT,
+        }
+    }
+}
+
+// The positions a game has been through, counted by their position hash along with the player to
+// move, which is what the draw rules need to know about the game so far.
+pub struct GameHistory {
+    pub rules: DrawRules,
+    position_counts: HashMap<u64, usize>,
+    max_position_count: usize,
+    ply_count: usize,
+}
+
+impl GameHistory {
+    pub fn new(rules: DrawRules, board_state: &BoardState, player_index: usize) -> Self {
+        let mut history = GameHistory {
+            rules,
+            position_counts: HashMap::new(),
+            max_position_count: 0,
+            ply_count: 0,
+        };
+        history.add_position(board_state, player_index);
+        history
+    }
+
+    // Records the position after an action, with the index of the player to move next.
+    pub fn record(&mut self, board_state: &BoardState, player_index: usize) {
+        self.ply_count += 1;
+        self.add_position(board_state, player_index);
+    }
+
+    pub fn get_ply_count(&self) -> usize {
+        self.ply_count
+    }
+
+    pub fn get_result(&self, board_state: &BoardState) -> Option<GameResult> {
+        if let Some(winner) = get_winner(board_state) {
+            return Some(GameResult::Win(winner));
+        }
+        let is_repeated = self.rules.repetition_count > 0 && self.max_position_count >= self.rules.repetition_count;
+        let is_too_long = self.rules.max_ply_count > 0 && self.ply_count >= self.rules.max_ply_count;
+        if is_repeated || is_too_long {
+            return Some(GameResult::Draw);
+        }
+        None
+    }
+
+    fn add_position(&mut self, board_state: &BoardState, player_index: usize) {
+        let count = self
+            .position_counts
+            .entry(board_state.get_position_hash(player_index))
+            .or_insert(0);
+        *count += 1;
+        self.max_position_count = self.max_position_count.max(*count);
+    }
+}
+
+pub fn get_winner(board_state: &BoardState) -> Option<usize> {
+    (0..2).find(|&player_index| board_state.get_player_distance(player_index) == 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::action::Action;
+    use crate::vector2::Vector2;
+
+    fn play(history: &mut GameHistory, board_state: &mut BoardState, moves: &[(isize, isize)]) {
+        let mut player_index = 0;
+        for &(x, y) in moves {
+            Action::Move(Vector2::new(x, y)).apply(board_state, player_index);
+            player_index = 1 - player_index;
+            history.record(board_state, player_index);
+        }
+    }
+
+    #[test]
+    fn draws_on_repetition() {
+        let mut board_state = BoardState::new();
+        let mut history = GameHistory::new(DrawRules::default(), &board_state, 0);
+        let shuffle = [(4, 1), (4, 7), (4, 0), (4, 8)];
+
+        play(&mut history, &mut board_state, &shuffle);
+        assert_eq!(None, history.get_result(&board_state));
+
+        play(&mut history, &mut board_state, &shuffle);
+        assert_eq!(Some(GameResult::Draw), history.get_result(&board_state));
+        assert_eq!(8, history.get_ply_count());
+    }
+
+    #[test]
+    fn draws_after_max_ply_count() {
+        let rules = DrawRules {
+            repetition_count: DEFAULT_REPETITION_COUNT,
+            max_ply_count: 3,
+        };
+        let mut board_state = BoardState::new();
+        let mut history = GameHistory::new(rules, &board_state, 0);
+
+        play(&mut history, &mut board_state, &[(4, 1), (4, 7)]);
+        assert_eq!(None, history.get_result(&board_state));
+
+        play(&mut history, &mut board_state, &[(4, 2)]);
+        assert_eq!(Some(GameResult::Draw), history.get_result(&board_state));
+    }
+
+    #[test]
+    fn zero_turns_rules_off() {
+        let rules = DrawRules::new(0, 0).unwrap();
+        let mut board_state = BoardState::new();
+        let mut history = GameHistory::new(rules, &board_state, 0);
+        let shuffle = [(4, 1), (4, 7), (4, 0), (4, 8)];
+
+        for _ in 0..3 {
+            play(&mut history, &mut board_state, &shuffle);
+        }
+
+        assert_eq!(None, history.get_result(&board_state));
+    }
+
+    #[test]
+    fn rejects_single_repetition() {
+        assert_eq!(true, DrawRules::new(1, 10).is_err());
+        assert_eq!(Ok(DrawRules::default()), DrawRules::new(3, 200));
+    }
+
+    #[test]
+    fn win_is_reported_before_draw() {
+        let rules = DrawRules {
+            repetition_count: DEFAULT_REPETITION_COUNT,
+            max_ply_count: 1,
+        };
+        let mut board_state = BoardState::new();
+        board_state.set_player_position(0, Vector2::new(3, 7));
+        let mut history = GameHistory::new(rules, &board_state, 0);
+
+        play(&mut history, &mut board_state, &[(3, 8)]);
+
+        assert_eq!(Some(GameResult::Win(0)), history.get_result(&board_state));
+    }
+}
@@ -0,0 +1,432 @@
+use crate::action::{Action, UndoRecord};
+use crate::board_state::{BoardState, WallUndoStack};
+use crate::endgame_solver::EndgameSolver;
+use crate::minimax_player::{
+    evaluate_with, get_aspiration_window, get_null_window_threshold, is_better_child, order_actions,
+    order_killer_first, score_result, set_killer_action, Evaluation, INFINITE_SCORE,
+};
+use crate::player::check_can_act;
+use crate::validation::*;
+
+use serde::Serialize;
+use tsify::Tsify;
+
+// The same search as `MinimaxPlayer::search`, but with the recursion kept on an explicit stack so
+// that it can stop after any node and carry on from there later. This lets the browser search a
+// little at a time between frames instead of blocking the page until the search is done. Like
+// `MinimaxPlayer` it makes and unmakes actions on a single board, which is at the position of the
+// innermost node between steps.
+pub struct IncrementalSearch {
+    board_state: BoardState,
+    player_index: usize,
+    max_depth: usize,
+    evaluation: Evaluation,
+    depth: usize,
+    // The window the current iteration's root was searched with.
+    window: (i32, i32),
+    nodes: usize,
+    stack: Vec<SearchFrame>,
+    progress: SearchProgress,
+    undo_stack: WallUndoStack,
+    killer_actions: Vec<Option<Action>>,
+}
+
+// The result of the deepest completed iteration, which is the best action so far.
+#[derive(Clone, Debug, PartialEq, Serialize, Tsify)]
+pub struct SearchProgress {
+    pub depth: usize,
+    pub max_depth: usize,
+    pub nodes: usize,
+    pub best_action: Option<Action>,
+    pub score: Option<i32>,
+    pub principal_variation: Vec<Action>,
+    pub is_finished: bool,
+}
+
+// A node whose children are still being searched. Scores are from the side of the player to move,
+// as in `MinimaxBoardNode::build_children`.
+struct SearchFrame {
+    // Takes back the action that led to this node when it is left, or none at the root.
+    undo_record: Option<UndoRecord>,
+    player_index: usize,
+    depth: usize,
+    original_alpha: i32,
+    alpha: i32,
+    beta: i32,
+    // Each action with its index in generated order, in the order they are searched.
+    actions: Vec<(usize, Action)>,
+    next_action_index: usize,
+    // The generated index of the best action so far, or `usize::MAX` before any child is scored.
+    best_action_index: usize,
+    score: i32,
+    principal_variation: Vec<Action>,
+    // The bound of the null window the child being searched has, if any, and whether the child
+    // beat it so that it has to be searched again with the full window.
+    null_window_threshold: Option<i32>,
+    needs_research: bool,
+}
+
+impl IncrementalSearch {
+    pub fn new(
+        board_state: &BoardState,
+        player_index: usize,
+        max_depth: usize,
+        evaluation: Evaluation,
+    ) -> Result<Self, String> {
+        check_can_act(board_state, player_index)?;
+        let max_depth = max_depth.max(1);
+        let mut search = IncrementalSearch {
+            board_state: *board_state,
+            player_index,
+            max_depth,
+            evaluation,
+            depth: 0,
+            window: (-INFINITE_SCORE, INFINITE_SCORE),
+            nodes: 0,
+            stack: Vec::new(),
+            progress: SearchProgress {
+                depth: 0,
+                max_depth,
+                nodes: 0,
+                best_action: None,
+                score: None,
+                principal_variation: Vec::new(),
+                is_finished: false,
+            },
+            undo_stack: WallUndoStack::default(),
+            killer_actions: Vec::new(),
+        };
+        // Positions without walls left are solved exactly, as in `MinimaxPlayer::take_action`. A
+        // drawn position has no line to play, so it is searched instead.
+        let solution = EndgameSolver::solve(board_state, player_index).filter(|solution| !solution.line.is_empty());
+        if let Some(solution) = solution {
+            search.progress.best_action = solution.line.first().copied();
+            search.progress.score = Some(score_result(solution.result));
+            search.progress.principal_variation = solution.line;
+            search.progress.is_finished = true;
+        } else {
+            search.start_iteration(1);
+            search.progress.nodes = search.nodes;
+        }
+        Ok(search)
+    }
+
+    pub fn get_player_index(&self) -> usize {
+        self.player_index
+    }
+
+    pub fn get_progress(&self) -> &SearchProgress {
+        &self.progress
+    }
+
+    // Searches until roughly `max_nodes` more nodes have been visited or the search is finished.
+    pub fn advance(&mut self, max_nodes: usize) -> &SearchProgress {
+        let start_nodes = self.nodes;
+        while !self.progress.is_finished && self.nodes - start_nodes < max_nodes {
+            self.step();
+        }
+        self.progress.nodes = self.nodes;
+        &self.progress
+    }
+
+    fn start_iteration(&mut self, depth: usize) {
+        self.depth = depth;
+        self.window = get_aspiration_window(depth, self.progress.score.unwrap_or(0));
+        self.killer_actions.clear();
+        self.enter_root();
+    }
+
+    fn enter_root(&mut self) {
+        let (alpha, beta) = self.window;
+        self.enter(None, self.player_index, self.depth, alpha, beta);
+    }
+
+    // Enters the next child of the innermost node, or leaves the node once it has no more.
+    fn step(&mut self) {
+        let frame = self.stack.last_mut().unwrap();
+        if frame.needs_research {
+            frame.needs_research = false;
+            let threshold = frame.null_window_threshold.take().unwrap();
+            // The child took its action back when it was left, so it is made again.
+            let (_, action) = frame.actions[frame.next_action_index - 1];
+            let undo_record = action.make(&mut self.board_state, frame.player_index, &mut self.undo_stack);
+            let (player_index, depth) = (1 - frame.player_index, frame.depth - 1);
+            let (alpha, beta) = (-frame.beta, -threshold);
+            self.enter(Some(undo_record), player_index, depth, alpha, beta);
+            return;
+        }
+        while frame.next_action_index < frame.actions.len() && frame.alpha < frame.beta {
+            let (index, action) = frame.actions[frame.next_action_index];
+            frame.next_action_index += 1;
+            let undo_record = action.make(&mut self.board_state, frame.player_index, &mut self.undo_stack);
+            if is_either_player_trapped(&self.board_state) {
+                undo_record.unmake(&mut self.board_state, &mut self.undo_stack);
+            } else {
+                let (player_index, depth) = (1 - frame.player_index, frame.depth - 1);
+                frame.null_window_threshold = if frame.best_action_index == usize::MAX {
+                    None
+                } else {
+                    Some(get_null_window_threshold(
+                        index,
+                        frame.best_action_index,
+                        frame.score,
+                        frame.original_alpha,
+                        frame.alpha,
+                    ))
+                };
+                let (alpha, beta) = match frame.null_window_threshold {
+                    Some(threshold) => (-threshold - 1, -threshold),
+                    None => (-frame.beta, -frame.alpha),
+                };
+                self.enter(Some(undo_record), player_index, depth, alpha, beta);
+                return;
+            }
+        }
+        let mut frame = self.stack.pop().unwrap();
+        if let Some(undo_record) = frame.undo_record.take() {
+            undo_record.unmake(&mut self.board_state, &mut self.undo_stack);
+        }
+        match self.stack.last_mut() {
+            Some(_) => self.add_child_score(-frame.score, frame.principal_variation),
+            None => self.complete_iteration(frame),
+        }
+    }
+
+    // Enters the node the undo record's action has just made on the board. A leaf is scored and
+    // left straight away.
+    fn enter(&mut self, undo_record: Option<UndoRecord>, player_index: usize, depth: usize, alpha: i32, beta: i32) {
+        self.nodes += 1;
+        let opp_distance = self.board_state.get_player_distance(1 - player_index);
+        let player_distance = self.board_state.get_player_distance(player_index);
+        if player_distance == 0 || opp_distance == 0 || depth == 0 {
+            let score = evaluate_with(self.evaluation, &self.board_state, player_index, self.depth - depth);
+            if let Some(undo_record) = undo_record {
+                undo_record.unmake(&mut self.board_state, &mut self.undo_stack);
+            }
+            self.add_child_score(-score, Vec::new());
+            return;
+        }
+        let mut actions = get_valid_move_actions(&self.board_state, player_index);
+        actions.append(&mut get_valid_block_actions(&self.board_state, player_index));
+        let ply = self.depth - depth;
+        let actions = if depth > 1 && alpha + 1 < beta {
+            order_actions(
+                &mut self.board_state,
+                player_index,
+                ply,
+                actions,
+                self.evaluation,
+                &mut self.undo_stack,
+            )
+        } else {
+            order_killer_first(actions, self.killer_actions.get(ply).copied().flatten())
+        };
+        self.stack.push(SearchFrame {
+            undo_record,
+            player_index,
+            depth,
+            original_alpha: alpha,
+            alpha,
+            beta,
+            actions,
+            next_action_index: 0,
+            best_action_index: usize::MAX,
+            score: -INFINITE_SCORE,
+            principal_variation: Vec::new(),
+            null_window_threshold: None,
+            needs_research: false,
+        });
+    }
+
+    // Passes a child's score to the innermost frame, and remembers the child's action as the killer
+    // at that frame's ply when it caused a cutoff.
+    fn add_child_score(&mut self, score: i32, principal_variation: Vec<Action>) {
+        let frame = self.stack.last_mut().unwrap();
+        if frame.add_child_score(score, principal_variation) {
+            let (_, action) = frame.actions[frame.next_action_index - 1];
+            set_killer_action(&mut self.killer_actions, self.depth - frame.depth, action);
+        }
+    }
+
+    fn complete_iteration(&mut self, root: SearchFrame) {
+        // A score outside the aspiration window is only a bound, so the depth is searched again
+        // with that side of the window opened.
+        let (alpha, beta) = self.window;
+        if root.score <= alpha {
+            self.window.0 = -INFINITE_SCORE;
+            self.enter_root();
+            return;
+        }
+        if root.score >= beta {
+            self.window.1 = INFINITE_SCORE;
+            self.enter_root();
+            return;
+        }
+        self.progress.depth = self.depth;
+        self.progress.best_action = root.principal_variation.first().copied();
+        self.progress.score = Some(root.score);
+        self.progress.principal_variation = root.principal_variation;
+        if self.depth < self.max_depth {
+            self.start_iteration(self.depth + 1);
+        } else {
+            self.progress.is_finished = true;
+        }
+    }
+}
+
+impl SearchFrame {
+    // Takes the score of the child just searched, from this frame's side, and returns whether it
+    // caused a cutoff.
+    fn add_child_score(&mut self, score: i32, mut principal_variation: Vec<Action>) -> bool {
+        if let Some(threshold) = self.null_window_threshold {
+            if self.depth > 2 && score > threshold && score < self.beta {
+                self.needs_research = true;
+                return false;
+            }
+        }
+        let (index, action) = self.actions[self.next_action_index - 1];
+        if is_better_child(score, index, self.score, self.best_action_index) {
+            self.score = score;
+            self.best_action_index = index;
+            self.principal_variation = vec![action];
+            self.principal_variation.append(&mut principal_variation);
+        }
+        self.alpha = self.alpha.max(self.score);
+        self.alpha >= self.beta
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::iterative_search::SearchLimits;
+    use crate::minimax_player::MinimaxPlayer;
+    use crate::notation::parse_position;
+
+    fn search_to_end(board_state: &BoardState, player_index: usize, max_depth: usize, step: usize) -> SearchProgress {
+        let mut search = IncrementalSearch::new(board_state, player_index, max_depth, Evaluation::Distance).unwrap();
+        while !search.advance(step).is_finished {}
+        search.get_progress().clone()
+    }
+
+    #[test]
+    fn matches_minimax_search() {
+        // Searching three plies deep is the least that searches again after null windows.
+        let positions = [
+            ("-.e1e9.10.10.1", 2),
+            ("d4hE6V.e3e7.9.9.1", 3),
+            ("c7hf7hD2VG3V.d5e6.8.8.2", 2),
+        ];
+        for &(position, depth) in positions.iter() {
+            let (board_state, player_index) = parse_position(position).unwrap();
+            let limits = SearchLimits {
+                max_depth: depth,
+                time_limit: None,
+                stop: None,
+            };
+            let info = MinimaxPlayer::search(&board_state, player_index, &limits, &mut |_| {});
+
+            let progress = search_to_end(&board_state, player_index, depth, 7);
+
+            assert_eq!(info.depth, progress.depth, "{}", position);
+            assert_eq!(info.nodes, progress.nodes, "{}", position);
+            assert_eq!(Some(info.score), progress.score, "{}", position);
+            assert_eq!(info.principal_variation, progress.principal_variation, "{}", position);
+            assert_eq!(
+                MinimaxPlayer::take_action(&board_state, player_index, depth).unwrap(),
+                progress.best_action.unwrap(),
+                "{}",
+                position
+            );
+        }
+    }
+
+    #[test]
+    fn matches_minimax_player_with_race_evaluation() {
+        for &position in ["-.e1e9.10.10.1", "d4hE6V.e3e7.9.9.1", "c7hf7hD2VG3V.d5e6.8.8.2"].iter() {
+            let (board_state, player_index) = parse_position(position).unwrap();
+            let report =
+                MinimaxPlayer::take_action_with_evaluation(&board_state, player_index, 3, Evaluation::Race).unwrap();
+
+            let mut search = IncrementalSearch::new(&board_state, player_index, 3, Evaluation::Race).unwrap();
+            while !search.advance(7).is_finished {}
+            let progress = search.get_progress();
+
+            assert_eq!(Some(report.score), progress.score, "{}", position);
+            assert_eq!(Some(report.best_action), progress.best_action, "{}", position);
+            assert_eq!(report.principal_variation, progress.principal_variation, "{}", position);
+        }
+    }
+
+    #[test]
+    fn result_does_not_depend_on_step_size() {
+        let board_state = BoardState::new();
+
+        assert_eq!(
+            search_to_end(&board_state, 0, 2, 1),
+            search_to_end(&board_state, 0, 2, 1000)
+        );
+    }
+
+    #[test]
+    fn search_restores_board() {
+        let (board_state, player_index) = parse_position("c7hf7hD2VG3V.d5e6.8.8.2").unwrap();
+        let mut search = IncrementalSearch::new(&board_state, player_index, 3, Evaluation::Distance).unwrap();
+
+        while !search.advance(13).is_finished {}
+
+        assert_eq!(true, search.stack.is_empty());
+        assert_eq!(true, search.board_state == board_state);
+        assert_eq!(0, search.undo_stack.len());
+    }
+
+    #[test]
+    fn advance_stops_after_node_budget() {
+        let mut search = IncrementalSearch::new(&BoardState::new(), 0, 3, Evaluation::Distance).unwrap();
+        let start_nodes = search.get_progress().nodes;
+
+        let progress = search.advance(10);
+
+        assert_eq!(start_nodes + 10, progress.nodes);
+        assert_eq!(false, progress.is_finished);
+        assert_eq!(None, progress.best_action);
+    }
+
+    #[test]
+    fn reports_completed_iterations() {
+        let mut search = IncrementalSearch::new(&BoardState::new(), 0, 2, Evaluation::Distance).unwrap();
+
+        let mut depths = Vec::new();
+        while !search.get_progress().is_finished {
+            let depth = search.advance(50).depth;
+            if depths.last() != Some(&depth) {
+                depths.push(depth);
+            }
+        }
+
+        assert_eq!(vec![0, 1, 2], depths);
+    }
+
+    #[test]
+    fn solves_positions_without_walls() {
+        let (board_state, player_index) = parse_position("-.e8a5.0.0.1").unwrap();
+
+        let search = IncrementalSearch::new(&board_state, player_index, 2, Evaluation::Distance).unwrap();
+
+        assert_eq!(true, search.get_progress().is_finished);
+        assert_eq!(
+            MinimaxPlayer::take_action(&board_state, player_index, 2).unwrap(),
+            search.get_progress().best_action.unwrap()
+        );
+    }
+
+    #[test]
+    fn new_fails_when_game_is_over() {
+        let (board_state, _) = parse_position("-.e9e5.10.10.2").unwrap();
+
+        assert_eq!(
+            Some(String::from("The game is over")),
+            IncrementalSearch::new(&board_state, 1, 2, Evaluation::Distance).err()
+        );
+    }
+}
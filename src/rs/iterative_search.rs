@@ -0,0 +1,75 @@
+use crate::action::Action;
+use crate::board_state::BoardState;
+use crate::minimax_player::{get_aspiration_window, MinimaxBoardNode, MinimaxPlayer, SearchContext, INFINITE_SCORE};
+
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+use std::time::Duration;
+use web_time::Instant;
+
+// Searching deeper until a time limit runs out or the search is stopped, for the engine protocol.
+// The browser searches a step at a time with `IncrementalSearch` instead, so this is only built
+// into the binary, and into the library's tests to check the other searches against.
+pub struct SearchLimits {
+    pub max_depth: usize,
+    pub time_limit: Option<Duration>,
+    pub stop: Option<Arc<AtomicBool>>,
+}
+
+// The result of the deepest completed iteration of a search.
+#[derive(Clone)]
+pub struct SearchInfo {
+    pub depth: usize,
+    pub score: i32,
+    pub nodes: usize,
+    pub principal_variation: Vec<Action>,
+}
+
+impl MinimaxPlayer {
+    // Searches one ply deeper on each iteration until the depth limit is reached or the search
+    // is stopped. `on_iteration` is called after each completed iteration. The first iteration
+    // always runs to completion so there is always an action to return.
+    pub fn search(
+        board_state: &BoardState,
+        player_index: usize,
+        limits: &SearchLimits,
+        on_iteration: &mut dyn FnMut(&SearchInfo),
+    ) -> SearchInfo {
+        let deadline = limits.time_limit.map(|time_limit| Instant::now() + time_limit);
+        let mut context = SearchContext::new(None, None);
+        let mut info = SearchInfo {
+            depth: 0,
+            score: 0,
+            nodes: 0,
+            principal_variation: Vec::new(),
+        };
+        let mut board_state = *board_state;
+        for depth in 1..=limits.max_depth.max(1) {
+            let (mut alpha, mut beta) = get_aspiration_window(depth, info.score);
+            let (score, principal_variation) = loop {
+                let mut node = MinimaxBoardNode::new(&mut board_state, player_index, 0);
+                node.build_children(depth, alpha, beta, &mut context);
+                if node.score <= alpha && !context.aborted {
+                    alpha = -INFINITE_SCORE;
+                } else if node.score >= beta && !context.aborted {
+                    beta = INFINITE_SCORE;
+                } else {
+                    break (node.score, node.principal_variation);
+                }
+            };
+            if context.aborted {
+                break;
+            }
+            info = SearchInfo {
+                depth,
+                score,
+                nodes: context.nodes,
+                principal_variation,
+            };
+            on_iteration(&info);
+            context = SearchContext::new(deadline, limits.stop.clone());
+            context.nodes = info.nodes;
+        }
+        info
+    }
+}
@@ -1,23 +1,50 @@
 mod action;
 mod board_state;
+mod endgame_solver;
+mod game_history;
+mod incremental_search;
+#[cfg(test)]
+mod iterative_search;
 mod minimax_player;
+mod notation;
+mod opening_book;
+#[cfg(test)]
+mod opening_book_builder;
+mod player;
+mod race;
 mod random_player;
 mod shortest_path_player;
+mod shortest_paths;
 mod validation;
 mod vector2;
+mod wall_impact;
 mod wall_orientation;
+mod zobrist;
 
 use crate::action::Action;
 use crate::board_state::BoardState;
-use crate::minimax_player::MinimaxPlayer;
+use crate::game_history::{DrawRules, GameHistory, GameResult};
+use crate::incremental_search::{IncrementalSearch, SearchProgress};
+use crate::minimax_player::{Analysis, Evaluation, MinimaxPlayer, SearchReport};
+use crate::notation::{format_action, format_position, parse_position};
+use crate::opening_book::OpeningBook;
+use crate::player::{check_can_act, Player};
 use crate::random_player::RandomPlayer;
 use crate::shortest_path_player::ShortestPathPlayer;
+use crate::shortest_paths::ShortestPaths;
 use crate::validation::*;
 use crate::vector2::Vector2;
+use crate::wall_impact::WallImpact;
 use crate::wall_orientation::WallOrientation;
 
-use std::sync::Mutex;
+use serde::Serialize;
+use std::error::Error;
+use std::fmt;
+use std::sync::{Mutex, MutexGuard};
+use std::time::Duration;
+use tsify::Tsify;
 use wasm_bindgen::prelude::*;
+use web_time::Instant;
 
 #[macro_use]
 extern crate lazy_static;
@@ -27,7 +54,8 @@ extern crate lazy_static;
 static ALLOC: wee_alloc::WeeAlloc = wee_alloc::WeeAlloc::INIT;
 
 lazy_static! {
-    static ref BOARD_STATE: Mutex<BoardState> = Mutex::new(BoardState::new());
+    static ref GAME: Mutex<Game> = Mutex::new(Game::new());
+    static ref OPENING_BOOK: Mutex<Option<OpeningBook>> = Mutex::new(None);
 }
 
 #[wasm_bindgen]
@@ -35,74 +63,666 @@ extern "C" {
     fn alert(s: &str);
 }
 
+// How many nodes a timed search step visits between reads of the clock.
+const NODES_PER_TIME_CHECK: usize = 256;
+
+// Panics are logged to the console with their message and location rather than only showing up as
+// an `unreachable` trap.
+#[wasm_bindgen(start)]
+pub fn start() {
+    console_error_panic_hook::set_once();
+}
+
+// Every export returns errors as JS `Error`s. Converting to `JsError` needs a JS environment, so the
+// Rust side of the API uses this instead and the conversion happens with `?` at the boundary.
+#[derive(Debug, PartialEq)]
+pub struct GameError(String);
+
+impl fmt::Display for GameError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl Error for GameError {}
+
+// Values are returned to JS as objects with TypeScript definitions generated from the Rust types.
+// wasm-bindgen can't describe a list of them directly, so lists get a type of their own.
+#[derive(Serialize, Tsify)]
+pub struct ActionList(Vec<Action>);
+
+#[derive(Serialize, Tsify)]
+pub struct WallImpactList(Vec<WallImpact>);
+
+type JsAction = <Action as Tsify>::JsType;
+type JsActionList = <ActionList as Tsify>::JsType;
+type JsAnalysis = <Analysis as Tsify>::JsType;
+type JsBoardState = <BoardState as Tsify>::JsType;
+type JsGameResult = <GameResult as Tsify>::JsType;
+type JsSearchProgress = <SearchProgress as Tsify>::JsType;
+type JsSearchReport = <SearchReport as Tsify>::JsType;
+type JsShortestPaths = <ShortestPaths as Tsify>::JsType;
+type JsWallImpactList = <WallImpactList as Tsify>::JsType;
+
+// Missing values are converted to null rather than undefined to match the generated definitions.
+fn to_js<T: Serialize + Tsify>(value: &T) -> Result<T::JsType, JsError> {
+    let serializer = serde_wasm_bindgen::Serializer::json_compatible();
+    Ok(value.serialize(&serializer)?.unchecked_into())
+}
+
+// A game with its own board and bots, so that a page can run several games side by side. The free
+// functions below act on a single shared game for compatibility.
+#[wasm_bindgen]
+pub struct Game {
+    board_state: BoardState,
+    history: GameHistory,
+    players: [Box<dyn Player + Send>; 2],
+    opening_book_enabled: [bool; 2],
+    // The evaluation `start_search` uses for each player, as set with `set_minimax_player`.
+    minimax_evaluations: [Evaluation; 2],
+    search: Option<IncrementalSearch>,
+    last_report: Option<SearchReport>,
+}
+
+#[wasm_bindgen]
+impl Game {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Game {
+        Game {
+            board_state: BoardState::new(),
+            history: GameHistory::new(DrawRules::default(), &BoardState::new(), 0),
+            players: [Box::new(MinimaxPlayer::new(2)), Box::new(ShortestPathPlayer::new(0.5))],
+            opening_book_enabled: [false; 2],
+            minimax_evaluations: [Evaluation::Distance; 2],
+            search: None,
+            last_report: None,
+        }
+    }
+
+    pub fn get_board(&self) -> Result<JsBoardState, JsError> {
+        to_js(&self.board_state)
+    }
+
+    pub fn is_game_over(&self) -> bool {
+        self.history.get_result(&self.board_state).is_some()
+    }
+
+    // Null while the game is still going.
+    pub fn get_result(&self) -> Result<Option<JsGameResult>, JsError> {
+        self.history
+            .get_result(&self.board_state)
+            .as_ref()
+            .map(to_js)
+            .transpose()
+    }
+
+    // The number of actions played since the board was reset or a position was loaded.
+    pub fn get_ply_count(&self) -> usize {
+        self.history.get_ply_count()
+    }
+
+    // The game is drawn once a position comes up `repetition_count` times with the same player to
+    // move, or after `max_ply_count` actions, where 0 turns either rule off. A repetition count of 1
+    // is an error, since it would draw every game at once. The new rules apply to the game so far.
+    pub fn set_draw_rules(&mut self, repetition_count: usize, max_ply_count: usize) -> Result<(), JsError> {
+        self.history.rules = DrawRules::new(repetition_count, max_ply_count).map_err(GameError)?;
+        Ok(())
+    }
+
+    pub fn reset_board(&mut self) {
+        self.board_state = BoardState::new();
+        self.history = GameHistory::new(self.history.rules, &self.board_state, 0);
+        self.search = None;
+        self.last_report = None;
+    }
+
+    // Returns the position in the notation described in `notation.rs`, which can be shared as text.
+    pub fn get_position(&self, player_index: usize) -> Result<String, JsError> {
+        check_player_index(player_index)?;
+        Ok(format_position(&self.board_state, player_index))
+    }
+
+    // Replaces the board with the given position and returns the index of the player to move. The
+    // error says what is wrong with the position.
+    pub fn load_position(&mut self, position: &str) -> Result<usize, JsError> {
+        let (board_state, player_index) = parse_position(position).map_err(GameError)?;
+        self.board_state = board_state;
+        self.history = GameHistory::new(self.history.rules, &self.board_state, player_index);
+        self.search = None;
+        self.last_report = None;
+        Ok(player_index)
+    }
+
+    pub fn set_random_player(&mut self, player_index: usize, move_chance: f32) -> Result<(), JsError> {
+        Ok(self.set_player(player_index, Box::new(RandomPlayer::new(move_chance)))?)
+    }
+
+    pub fn set_shortest_path_player(&mut self, player_index: usize, move_chance: f32) -> Result<(), JsError> {
+        Ok(self.set_player(player_index, Box::new(ShortestPathPlayer::new(move_chance)))?)
+    }
+
+    // The race evaluation plays the races past the other pawn better but searches more slowly.
+    // `start_search` uses the evaluation chosen here for the player too.
+    pub fn set_minimax_player(
+        &mut self,
+        player_index: usize,
+        branch_depth: usize,
+        race_evaluation: bool,
+    ) -> Result<(), JsError> {
+        let evaluation = if race_evaluation {
+            Evaluation::Race
+        } else {
+            Evaluation::Distance
+        };
+        let player = MinimaxPlayer::with_evaluation(branch_depth, evaluation);
+        self.set_player(player_index, Box::new(player))?;
+        self.minimax_evaluations[player_index] = evaluation;
+        Ok(())
+    }
+
+    // Takes a turn with the bot set for the player.
+    pub fn take_turn(&mut self, player_index: usize) -> Result<JsAction, JsError> {
+        let (action, report) = self.choose_action(player_index)?;
+        self.play_action(action, report, player_index)
+    }
+
+    pub fn take_random_turn(&mut self, player_index: usize, move_chance: f32) -> Result<JsAction, JsError> {
+        self.take_turn_with(&mut RandomPlayer::new(move_chance), player_index)
+    }
+
+    pub fn take_shortest_path_turn(&mut self, player_index: usize, move_chance: f32) -> Result<JsAction, JsError> {
+        self.take_turn_with(&mut ShortestPathPlayer::new(move_chance), player_index)
+    }
+
+    pub fn take_minimax_turn(&mut self, player_index: usize, branch_depth: usize) -> Result<JsAction, JsError> {
+        self.take_turn_with(&mut MinimaxPlayer::new(branch_depth), player_index)
+    }
+
+    // Scores the legal actions of the player to move with a minimax search and returns the best
+    // `top_count` of them, and optionally the score of every legal wall for drawing a heatmap.
+    pub fn analyze(
+        &self,
+        player_index: usize,
+        branch_depth: usize,
+        top_count: usize,
+        include_wall_scores: bool,
+    ) -> Result<JsAnalysis, JsError> {
+        check_player_index(player_index)?;
+        check_can_act(&self.board_state, player_index).map_err(GameError)?;
+        to_js(&MinimaxPlayer::analyze(
+            &self.board_state,
+            player_index,
+            branch_depth,
+            top_count,
+            include_wall_scores,
+        ))
+    }
+
+    // A minimax search that runs a step at a time, so that the page stays responsive while it
+    // searches. Start it, call `advance_search` or `advance_search_for` until the progress says it
+    // is finished, then play the best action with `finish_search`. Starting a new search or
+    // changing the board cancels the current one. The search evaluates positions the way the
+    // player's minimax bot was set to, or by distance for other bots.
+    pub fn start_search(&mut self, player_index: usize, branch_depth: usize) -> Result<(), JsError> {
+        check_player_index(player_index)?;
+        self.check_not_drawn()?;
+        let evaluation = self.minimax_evaluations[player_index];
+        let search =
+            IncrementalSearch::new(&self.board_state, player_index, branch_depth, evaluation).map_err(GameError)?;
+        self.search = Some(search);
+        Ok(())
+    }
+
+    pub fn advance_search(&mut self, node_count: usize) -> Result<JsSearchProgress, JsError> {
+        to_js(self.get_search()?.advance(node_count))
+    }
+
+    pub fn advance_search_for(&mut self, milliseconds: f64) -> Result<JsSearchProgress, JsError> {
+        let deadline = Instant::now() + Duration::from_secs_f64(milliseconds.max(0.0) / 1000.0);
+        let search = self.get_search()?;
+        while !search.advance(NODES_PER_TIME_CHECK).is_finished && Instant::now() < deadline {}
+        to_js(search.get_progress())
+    }
+
+    pub fn get_search_progress(&mut self) -> Result<JsSearchProgress, JsError> {
+        to_js(self.get_search()?.get_progress())
+    }
+
+    pub fn cancel_search(&mut self) {
+        self.search = None;
+    }
+
+    pub fn finish_search(&mut self) -> Result<JsAction, JsError> {
+        to_js(&self.play_search_action()?)
+    }
+
+    // The report of the search behind the last bot turn, with its score, principal variation and
+    // statistics. It is null when the last action wasn't searched for, such as a book move or an
+    // action played from the page.
+    pub fn get_last_report(&self) -> Result<Option<JsSearchReport>, JsError> {
+        self.last_report.as_ref().map(to_js).transpose()
+    }
+
+    pub fn set_opening_book_enabled(&mut self, player_index: usize, enabled: bool) -> Result<(), JsError> {
+        check_player_index(player_index)?;
+        self.opening_book_enabled[player_index] = enabled;
+        Ok(())
+    }
+
+    // One shortest route from the player's pawn to their goal row, every cell on any shortest route
+    // and how many routes there are, for highlighting them on the board.
+    pub fn get_shortest_paths(&self, player_index: usize) -> Result<JsShortestPaths, JsError> {
+        check_player_index(player_index)?;
+        to_js(&shortest_paths::get_shortest_paths(&self.board_state, player_index))
+    }
+
+    // What each wall that fits on the board would do to the players' distances if the player placed
+    // it, and whether they can. Working it out once per position is quick enough for previewing
+    // walls as the pointer hovers over them.
+    pub fn get_wall_impacts(&self, player_index: usize) -> Result<JsWallImpactList, JsError> {
+        check_player_index(player_index)?;
+        to_js(&WallImpactList(wall_impact::get_wall_impacts(
+            &self.board_state,
+            player_index,
+        )))
+    }
+
+    pub fn get_valid_actions(&self, player_index: usize) -> Result<JsActionList, JsError> {
+        check_player_index(player_index)?;
+        let mut valid_actions = Vec::<Action>::new();
+        valid_actions.append(&mut get_valid_move_actions(&self.board_state, player_index));
+        valid_actions.append(&mut get_valid_block_actions(&self.board_state, player_index));
+        to_js(&ActionList(valid_actions))
+    }
+
+    pub fn apply_move_action(&mut self, x: isize, y: isize, player_index: usize) -> Result<JsAction, JsError> {
+        to_js(&self.apply_action(Action::Move(Vector2::new(x, y)), player_index)?)
+    }
+
+    pub fn apply_block_action(
+        &mut self,
+        x: isize,
+        y: isize,
+        orientation: usize,
+        player_index: usize,
+    ) -> Result<JsAction, JsError> {
+        let wall_orientation = [WallOrientation::Horizontal, WallOrientation::Vertical]
+            .iter()
+            .copied()
+            .find(|wall_orientation| wall_orientation.get_api_index() == orientation)
+            .ok_or_else(|| JsError::new(&format!("Invalid wall orientation: {}", orientation)))?;
+        to_js(&self.apply_action(Action::Block(Vector2::new(x, y), wall_orientation), player_index)?)
+    }
+}
+
+impl Game {
+    fn set_player(&mut self, player_index: usize, player: Box<dyn Player + Send>) -> Result<(), GameError> {
+        check_player_index(player_index)?;
+        self.players[player_index] = player;
+        Ok(())
+    }
+
+    fn take_turn_with(&mut self, player: &mut dyn Player, player_index: usize) -> Result<JsAction, JsError> {
+        check_player_index(player_index)?;
+        self.check_not_drawn()?;
+        let (action, report) = match self.get_book_action(player_index)? {
+            Some(action) => (action, None),
+            None => choose_with_report(player, &self.board_state, player_index)?,
+        };
+        self.play_action(action, report, player_index)
+    }
+
+    fn choose_action(&mut self, player_index: usize) -> Result<(Action, Option<SearchReport>), GameError> {
+        check_player_index(player_index)?;
+        self.check_not_drawn()?;
+        let board_state = self.board_state;
+        match self.get_book_action(player_index)? {
+            Some(action) => Ok((action, None)),
+            None => choose_with_report(&mut *self.players[player_index], &board_state, player_index),
+        }
+    }
+
+    fn play_action(
+        &mut self,
+        action: Action,
+        report: Option<SearchReport>,
+        player_index: usize,
+    ) -> Result<JsAction, JsError> {
+        let action = self.apply_action(action, player_index)?;
+        self.last_report = report;
+        to_js(&action)
+    }
+
+    fn get_search(&mut self) -> Result<&mut IncrementalSearch, GameError> {
+        self.search
+            .as_mut()
+            .ok_or_else(|| GameError(String::from("No search has been started")))
+    }
+
+    fn play_search_action(&mut self) -> Result<Action, GameError> {
+        let search = self.get_search()?;
+        let progress = search.get_progress();
+        if !progress.is_finished {
+            return Err(GameError(String::from("The search hasn't finished")));
+        }
+        let action = progress
+            .best_action
+            .ok_or_else(|| GameError(String::from("The search found no action")))?;
+        let player_index = search.get_player_index();
+        self.apply_action(action, player_index)
+    }
+
+    // Actions are checked here because they can come straight from the page.
+    fn apply_action(&mut self, action: Action, player_index: usize) -> Result<Action, GameError> {
+        check_player_index(player_index)?;
+        check_can_act(&self.board_state, player_index).map_err(GameError)?;
+        self.check_not_drawn()?;
+        if !validate_action(&self.board_state, player_index, &action) {
+            return Err(GameError(format!("Illegal action: {}", format_action(&action))));
+        }
+        action.apply(&mut self.board_state, player_index);
+        self.history.record(&self.board_state, 1 - player_index);
+        self.search = None;
+        self.last_report = None;
+        Ok(action)
+    }
+
+    // Won games are caught by `check_can_act`, which the bots also use.
+    fn check_not_drawn(&self) -> Result<(), GameError> {
+        match self.history.get_result(&self.board_state) {
+            Some(GameResult::Draw) => Err(GameError(String::from("The game is drawn"))),
+            _ => Ok(()),
+        }
+    }
+
+    fn get_book_action(&self, player_index: usize) -> Result<Option<Action>, GameError> {
+        if !self.opening_book_enabled[player_index] {
+            return Ok(None);
+        }
+        let opening_book = lock(&OPENING_BOOK)?;
+        Ok(opening_book
+            .as_ref()
+            .and_then(|book| book.probe(&self.board_state, player_index)))
+    }
+}
+
+// Returns the action along with the report of the search behind it, for players that search.
+fn choose_with_report(
+    player: &mut dyn Player,
+    board_state: &BoardState,
+    player_index: usize,
+) -> Result<(Action, Option<SearchReport>), GameError> {
+    let action = player.choose_action(board_state, player_index).map_err(GameError)?;
+    Ok((action, player.get_last_report().cloned()))
+}
+
+fn check_player_index(player_index: usize) -> Result<(), GameError> {
+    if player_index > 1 {
+        return Err(GameError(format!("Invalid player index: {}", player_index)));
+    }
+    Ok(())
+}
+
+// A panic while the lock is held poisons it, and every later call reports that instead of trapping.
+fn lock<T>(mutex: &Mutex<T>) -> Result<MutexGuard<'_, T>, GameError> {
+    mutex
+        .lock()
+        .map_err(|_| GameError(String::from("The game is unusable after an earlier error")))
+}
+
+impl Default for Game {
+    fn default() -> Self {
+        Game::new()
+    }
+}
+
+#[wasm_bindgen]
+pub fn get_board() -> Result<JsBoardState, JsError> {
+    lock(&GAME)?.get_board()
+}
+
+#[wasm_bindgen]
+pub fn is_game_over() -> Result<bool, JsError> {
+    Ok(lock(&GAME)?.is_game_over())
+}
+
+#[wasm_bindgen]
+pub fn get_game_result() -> Result<Option<JsGameResult>, JsError> {
+    lock(&GAME)?.get_result()
+}
+
+#[wasm_bindgen]
+pub fn set_draw_rules(repetition_count: usize, max_ply_count: usize) -> Result<(), JsError> {
+    lock(&GAME)?.set_draw_rules(repetition_count, max_ply_count)
+}
+
 #[wasm_bindgen]
-pub fn get_board() -> String {
-    let board_state = &mut BOARD_STATE.lock().unwrap();
-    return String::from(serde_json::to_string(&**board_state).unwrap());
+pub fn reset_board() -> Result<(), JsError> {
+    lock(&GAME)?.reset_board();
+    Ok(())
 }
 
 #[wasm_bindgen]
-pub fn is_game_over() -> bool {
-    let board_state = &mut BOARD_STATE.lock().unwrap();
-    return board_state.get_player_distance(0) == 0 || board_state.get_player_distance(1) == 0;
+pub fn get_position(player_index: usize) -> Result<String, JsError> {
+    lock(&GAME)?.get_position(player_index)
 }
 
 #[wasm_bindgen]
-pub fn reset_board() {
-    let board_state = &mut BOARD_STATE.lock().unwrap();
-    **board_state = BoardState::new();
+pub fn load_position(position: &str) -> Result<usize, JsError> {
+    lock(&GAME)?.load_position(position)
 }
 
 #[wasm_bindgen]
-pub fn take_random_turn(player_index: usize, move_chance: f32) -> String {
-    let board_state = &mut BOARD_STATE.lock().unwrap();
-    let action = RandomPlayer::take_action(&board_state, player_index, move_chance);
-    action.apply(board_state, player_index);
-    return String::from(serde_json::to_string(&action).unwrap());
+pub fn take_random_turn(player_index: usize, move_chance: f32) -> Result<JsAction, JsError> {
+    lock(&GAME)?.take_random_turn(player_index, move_chance)
 }
 
 #[wasm_bindgen]
-pub fn take_shortest_path_turn(player_index: usize, move_chance: f32) -> String {
-    let board_state = &mut BOARD_STATE.lock().unwrap();
-    let action = ShortestPathPlayer::take_action(&board_state, player_index, move_chance);
-    action.apply(board_state, player_index);
-    return String::from(serde_json::to_string(&action).unwrap());
+pub fn take_shortest_path_turn(player_index: usize, move_chance: f32) -> Result<JsAction, JsError> {
+    lock(&GAME)?.take_shortest_path_turn(player_index, move_chance)
 }
 
 #[wasm_bindgen]
-pub fn take_minimax_turn(player_index: usize, branch_depth: usize) -> String {
-    let board_state = &mut BOARD_STATE.lock().unwrap();
-    let action = MinimaxPlayer::take_action(&board_state, player_index, branch_depth);
-    action.apply(board_state, player_index);
-    return String::from(serde_json::to_string(&action).unwrap());
+pub fn take_minimax_turn(player_index: usize, branch_depth: usize) -> Result<JsAction, JsError> {
+    lock(&GAME)?.take_minimax_turn(player_index, branch_depth)
 }
 
+// The opening book is shared by every game, but each game chooses which players use it.
 #[wasm_bindgen]
-pub fn get_valid_actions(player_index: usize) -> String {
-    let board_state = &mut BOARD_STATE.lock().unwrap();
-    let mut valid_actions = Vec::<Action>::new();
-    valid_actions.append(&mut get_valid_move_actions(&board_state, player_index));
-    valid_actions.append(&mut get_valid_block_actions(&board_state, player_index));
-    return String::from(serde_json::to_string(&valid_actions).unwrap());
+pub fn load_opening_book(bytes: &[u8]) -> Result<(), JsError> {
+    let book = OpeningBook::from_bytes(bytes).map_err(GameError)?;
+    *lock(&OPENING_BOOK)? = Some(book);
+    Ok(())
 }
 
 #[wasm_bindgen]
-pub fn apply_move_action(x: isize, y: isize, player_index: usize) -> String {
-    let action = Action::Move(Vector2::new(x, y));
-    let board_state = &mut BOARD_STATE.lock().unwrap();
-    action.apply(board_state, player_index);
-    return String::from(serde_json::to_string(&action).unwrap());
+pub fn set_opening_book_enabled(player_index: usize, enabled: bool) -> Result<(), JsError> {
+    lock(&GAME)?.set_opening_book_enabled(player_index, enabled)
 }
 
 #[wasm_bindgen]
-pub fn apply_block_action(x: isize, y: isize, orientation: usize, player_index: usize) -> String {
-    let wall_orientation = if orientation == 0 {
-        WallOrientation::Horizontal
-    } else {
-        WallOrientation::Vertical
-    };
-    let action = Action::Block(Vector2::new(x, y), wall_orientation);
-    let board_state = &mut BOARD_STATE.lock().unwrap();
-    action.apply(board_state, player_index);
-    return String::from(serde_json::to_string(&action).unwrap());
+pub fn get_shortest_paths(player_index: usize) -> Result<JsShortestPaths, JsError> {
+    lock(&GAME)?.get_shortest_paths(player_index)
+}
+
+#[wasm_bindgen]
+pub fn get_wall_impacts(player_index: usize) -> Result<JsWallImpactList, JsError> {
+    lock(&GAME)?.get_wall_impacts(player_index)
+}
+
+#[wasm_bindgen]
+pub fn get_valid_actions(player_index: usize) -> Result<JsActionList, JsError> {
+    lock(&GAME)?.get_valid_actions(player_index)
+}
+
+#[wasm_bindgen]
+pub fn apply_move_action(x: isize, y: isize, player_index: usize) -> Result<JsAction, JsError> {
+    lock(&GAME)?.apply_move_action(x, y, player_index)
+}
+
+#[wasm_bindgen]
+pub fn apply_block_action(x: isize, y: isize, orientation: usize, player_index: usize) -> Result<JsAction, JsError> {
+    lock(&GAME)?.apply_block_action(x, y, orientation, player_index)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn games_are_independent() {
+        let mut first = Game::new();
+        let second = Game::new();
+
+        first.apply_action(Action::Move(Vector2::new(4, 1)), 0).unwrap();
+
+        assert_eq!("-.e2e9.10.10.2", format_position(&first.board_state, 1));
+        assert_eq!("-.e1e9.10.10.1", format_position(&second.board_state, 0));
+    }
+
+    #[test]
+    fn choose_action_uses_player_bot() {
+        let mut game = Game::new();
+        game.set_player(0, Box::new(ShortestPathPlayer::new(1.0))).unwrap();
+
+        let action = game.choose_action(0).map(|(action, _)| action);
+
+        assert_eq!(Ok(Action::Move(Vector2::new(4, 1))), action);
+    }
+
+    #[test]
+    fn choose_action_reports_minimax_search() {
+        let mut game = Game::new();
+
+        let (action, report) = game.choose_action(0).unwrap();
+
+        assert_eq!(Some(action), report.map(|report| report.best_action));
+        assert_eq!(None, game.choose_action(1).unwrap().1);
+    }
+
+    #[test]
+    fn minimax_player_without_depth_still_acts() {
+        let mut game = Game::new();
+        game.set_player(0, Box::new(MinimaxPlayer::new(0))).unwrap();
+
+        let (action, report) = game.choose_action(0).unwrap();
+
+        assert_eq!(Some(action), report.map(|report| report.best_action));
+    }
+
+    #[test]
+    fn search_plays_minimax_action() {
+        let mut game = Game::new();
+        game.search = Some(IncrementalSearch::new(&game.board_state, 0, 2, Evaluation::Distance).unwrap());
+
+        assert_eq!(
+            Err(GameError(String::from("The search hasn't finished"))),
+            game.play_search_action()
+        );
+        while !game.get_search().unwrap().advance(100).is_finished {}
+        let action = game.play_search_action();
+
+        assert_eq!(
+            Ok(MinimaxPlayer::take_action(&BoardState::new(), 0, 2).unwrap()),
+            action
+        );
+        assert_eq!(true, game.search.is_none());
+    }
+
+    #[test]
+    fn search_uses_minimax_player_evaluation() {
+        let mut game = Game::new();
+        game.set_minimax_player(0, 3, true).ok().unwrap();
+
+        game.start_search(0, 3).ok().unwrap();
+        while !game.get_search().unwrap().advance(100).is_finished {}
+
+        let report = MinimaxPlayer::take_action_with_evaluation(&game.board_state, 0, 3, Evaluation::Race).unwrap();
+        let progress = game.get_search().unwrap().get_progress();
+        assert_eq!(Some(report.score), progress.score);
+        assert_eq!(report.principal_variation, progress.principal_variation);
+    }
+
+    #[test]
+    fn apply_action_rejects_illegal_actions() {
+        let mut game = Game::new();
+
+        assert_eq!(
+            Err(GameError(String::from("Illegal action: e3"))),
+            game.apply_action(Action::Move(Vector2::new(4, 2)), 0)
+        );
+        assert_eq!(
+            Err(GameError(String::from("Invalid player index: 2"))),
+            game.apply_action(Action::Move(Vector2::new(4, 1)), 2)
+        );
+        assert_eq!("-.e1e9.10.10.1", format_position(&game.board_state, 0));
+    }
+
+    #[test]
+    fn repeated_positions_draw_the_game() {
+        let mut game = Game::new();
+        let shuffle = [(4, 1), (4, 7), (4, 0), (4, 8)];
+        for _ in 0..2 {
+            for (i, &(x, y)) in shuffle.iter().enumerate() {
+                assert_eq!(false, game.is_game_over());
+                game.apply_action(Action::Move(Vector2::new(x, y)), i % 2).unwrap();
+            }
+        }
+
+        assert_eq!(true, game.is_game_over());
+        assert_eq!(Some(GameResult::Draw), game.history.get_result(&game.board_state));
+        assert_eq!(
+            Some(GameError(String::from("The game is drawn"))),
+            game.choose_action(0).err()
+        );
+        assert_eq!(
+            Err(GameError(String::from("The game is drawn"))),
+            game.apply_action(Action::Move(Vector2::new(4, 1)), 0)
+        );
+
+        game.reset_board();
+        assert_eq!(false, game.is_game_over());
+    }
+
+    #[test]
+    fn new_board_clears_last_report() {
+        let mut game = Game::new();
+        let report = MinimaxPlayer::take_action_with_report(&game.board_state, 0, 1).unwrap();
+        game.last_report = Some(report.clone());
+
+        game.reset_board();
+        assert_eq!(None, game.last_report);
+
+        game.last_report = Some(report);
+        game.load_position("-.e2e8.10.10.1").unwrap();
+        assert_eq!(None, game.last_report);
+    }
+
+    #[test]
+    fn draw_rules_limit_game_length() {
+        let mut game = Game::new();
+        game.set_draw_rules(3, 2).unwrap();
+
+        game.apply_action(Action::Move(Vector2::new(4, 1)), 0).unwrap();
+        assert_eq!(false, game.is_game_over());
+        game.apply_action(Action::Move(Vector2::new(4, 7)), 1).unwrap();
+
+        assert_eq!(true, game.is_game_over());
+        assert_eq!(2, game.get_ply_count());
+    }
+
+    #[test]
+    fn choose_action_fails_when_game_is_over() {
+        let mut game = Game::new();
+        game.board_state = parse_position("-.e9e5.10.10.2").unwrap().0;
+
+        assert_eq!(
+            Some(GameError(String::from("The game is over"))),
+            game.choose_action(1).err()
+        );
+        assert_eq!(
+            Some(GameError(String::from("Invalid player index: 2"))),
+            game.choose_action(2).err()
+        );
+    }
 }
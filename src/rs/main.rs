@@ -1,71 +1,159 @@
 mod action;
 mod board_state;
+mod endgame_solver;
+mod engine;
+mod external_player;
+mod game_history;
+mod iterative_search;
 mod minimax_player;
+mod notation;
+mod opening_book;
+mod opening_book_builder;
+#[cfg(feature = "parallel")]
+mod parallel_search;
+mod perft;
+mod player;
+mod race;
 mod random_player;
+mod search_benchmark;
 mod shortest_path_player;
+mod simulation;
 mod validation;
 mod vector2;
 mod wall_orientation;
+mod zobrist;
 
+use action::Action;
 use board_state::BoardState;
-use minimax_player::MinimaxPlayer;
-// use random_player::RandomPlayer;
+use board_state::WallUndoStack;
+use external_player::ExternalPlayer;
+use game_history::{DrawRules, GameHistory, GameResult, DEFAULT_MAX_PLY_COUNT, DEFAULT_REPETITION_COUNT};
+use minimax_player::{format_score, Evaluation, MinimaxPlayer, INFINITE_SCORE};
+use notation::{format_action, parse_position};
+use opening_book::OpeningBook;
+use player::Player;
+use random_player::RandomPlayer;
 use shortest_path_player::ShortestPathPlayer;
+use simulation::{play_games, SimulationOptions};
 
 #[macro_use]
 extern crate lazy_static;
 
-use std::time::Instant;
+use std::env;
+use std::fs;
+use std::process;
+use std::time::{Duration, Instant};
 
 fn main() {
-    let mut total_turn_count = 0;
+    let args: Vec<String> = env::args().skip(1).collect();
+    match args.first().map(|arg| arg.as_str()) {
+        Some("build-book") => build_book(&args[1..]),
+        Some("engine") => engine::run(),
+        Some("perft") => run_perft(&args[1..]),
+        Some("bench-search") => run_search_benchmark(&args[1..]),
+        Some("analyze") => run_analysis(&args[1..]),
+        _ => run_games(&args),
+    }
+}
 
-    let start = Instant::now();
-    let game_count = 20;
-    let mut player_1_wins = 0;
+// Plays a series of games between two players and reports the results.
+//
+// Usage: quoridor [--games <count>] [--player1 <player>] [--player2 <player>] [--book <path>]
+//                 [--timeout <ms>] [--threads <count>] [--jobs <count>] [--seed <seed>]
+//                 [--max-plies <count>] [--repetitions <count>] [--verbose] [--distances <player>]
+//
+// Players are minimax[:depth], minimax-race[:depth], shortest-path[:move chance],
+// random[:move chance] or engine:<command>, where the command starts a program that speaks the
+// engine protocol. A minimax-race player scores positions by racing the pawns rather than by their
+// distances alone. The timeout limits how long an engine may take for each move. In verbose mode
+// every action is printed along with the search report of minimax players and the board after it,
+// which shows the given player's distance to their goal from each cell with --distances 1 or 2.
+// Minimax players search on several threads when the binary is built with the parallel feature,
+// apart from minimax-race players, which always search on one.
+//
+// Games are played on as many worker threads as there are jobs, each with its own players. A run
+// with a given seed plays the same games and gives the same totals whatever the number of jobs,
+// apart from engines, which can't be seeded. Without a seed a random one is chosen and printed.
+//
+// A game is drawn after the maximum number of plies, 200 by default, or once a position comes up
+// as many times as the repetition count, 3 by default. Either can be set to 0 to turn it off.
+fn run_games(args: &[String]) {
+    let opening_book = get_option(args, "--book").map(|path| load_opening_book(&path));
+    let game_count = get_option(args, "--games").map_or(20, |arg| parse_number(&arg));
+    let timeout = Duration::from_millis(get_option(args, "--timeout").map_or(10000, |arg| parse_number(&arg)) as u64);
+    let thread_count = get_option(args, "--threads").map_or(1, |arg| parse_number(&arg));
+    let draw_rules = DrawRules::new(
+        get_option(args, "--repetitions").map_or(DEFAULT_REPETITION_COUNT, |arg| parse_number(&arg)),
+        get_option(args, "--max-plies").map_or(DEFAULT_MAX_PLY_COUNT, |arg| parse_number(&arg)),
+    )
+    .unwrap_or_else(|error| {
+        eprintln!("{}", error);
+        process::exit(1);
+    });
+    let options = SimulationOptions {
+        game_count,
+        job_count: get_option(args, "--jobs").map_or(1, |arg| parse_number(&arg)),
+        seed: get_option(args, "--seed").map_or_else(rand::random, |arg| parse_number(&arg) as u64),
+        draw_rules,
+        verbose: args.iter().any(|arg| arg == "--verbose"),
+        distance_player_index: get_option(args, "--distances").map(|arg| match parse_number(&arg) {
+            number @ 1..=2 => number - 1,
+            _ => {
+                eprintln!("Invalid player: {}", arg);
+                process::exit(1);
+            }
+        }),
+    };
+    let player_specs = [
+        get_option(args, "--player1").unwrap_or_else(|| String::from("minimax:3")),
+        get_option(args, "--player2").unwrap_or_else(|| String::from("shortest-path:0.5")),
+    ];
+    let create_players = || -> [Box<dyn Player>; 2] {
+        [
+            create_player(&player_specs[0], timeout, thread_count),
+            create_player(&player_specs[1], timeout, thread_count),
+        ]
+    };
 
-    println!("Playing {} Games", game_count);
+    let players = create_players();
+    println!(
+        "Playing {} Games ({} vs {})",
+        game_count,
+        players[0].get_name(),
+        players[1].get_name()
+    );
+    println!("Seed: {}", options.seed);
+    drop(players);
 
-    for i in 0..game_count {
-        let mut board_state = BoardState::new();
-        let mut player_index = 0;
-        let mut turn_count = 0;
-        loop {
-            let action = if player_index == 0 {
-                MinimaxPlayer::take_action(&board_state, player_index, 3)
-            //ShortestPathPlayer::take_action(&board_state, player_index, 0.5)
-            } else {
-                //MinimaxPlayer::take_action(&board_state, player_index, 3)
-                ShortestPathPlayer::take_action(&board_state, player_index, 0.5)
-            };
-            action.apply(&mut board_state, player_index);
-            turn_count += 1;
-            if board_state.get_player_distance(player_index) == 0 {
-                if player_index == 0 {
-                    player_1_wins += 1;
-                }
-                break;
-            }
-            player_index = 1 - player_index;
-        }
-        total_turn_count += turn_count;
-
-        if i % 1 == 0 {
-            println!(
-                "Completed {} ({:.1}%) Games",
-                i + 1,
-                (i + 1) as f32 / game_count as f32 * 100.0
-            );
-        }
-    }
+    let start = Instant::now();
+    let mut completed_count = 0;
+    let totals = play_games(&create_players, opening_book.as_ref(), &options, &mut |_, record| {
+        print!("{}", record.log);
+        completed_count += 1;
+        println!(
+            "Completed {} ({:.1}%) Games",
+            completed_count,
+            completed_count as f32 / game_count as f32 * 100.0
+        );
+    });
     let duration = start.elapsed().as_secs_f32();
     let games_per_sec = game_count as f32 / duration;
-    let turns_per_sec = total_turn_count as f32 / duration;
-    let player_2_wins = game_count - player_1_wins;
+    let turns_per_sec = totals.turn_count as f32 / duration;
+    let [player_1_wins, player_2_wins] = totals.wins;
+    let search_stats = totals.search_stats;
     println!("== Complete ==");
     println!("Time Elapsed: {:.2} s)", duration);
     println!("Game rate: {:.1} g/s)", games_per_sec);
     println!("Turn rate: {:.1} t/s)", turns_per_sec);
+    println!("Turns: {}", totals.turn_count);
+    if search_stats.nodes > 0 {
+        println!(
+            "Search rate: {} ({} nodes in {:.2} s)",
+            format_node_rate(search_stats.nodes, search_stats.elapsed_ms / 1000.0),
+            search_stats.nodes,
+            search_stats.elapsed_ms / 1000.0
+        );
+    }
     println!(
         "Player 1 wins: {} ({:.1}%))",
         player_1_wins,
@@ -76,4 +164,283 @@ fn main() {
         player_2_wins,
         player_2_wins as f32 / game_count as f32 * 100.0
     );
+    println!(
+        "Draws: {} ({:.1}%))",
+        totals.draws,
+        totals.draws as f32 / game_count as f32 * 100.0
+    );
+}
+
+fn create_player(spec: &str, timeout: Duration, thread_count: usize) -> Box<dyn Player> {
+    let (name, argument) = match spec.find(':') {
+        Some(index) => (&spec[..index], Some(&spec[index + 1..])),
+        None => (spec, None),
+    };
+    let parse_move_chance = |argument: Option<&str>| match argument {
+        Some(argument) => argument.parse::<f32>().unwrap_or_else(|_| {
+            eprintln!("Invalid move chance: {}", argument);
+            process::exit(1);
+        }),
+        None => 0.5,
+    };
+    match name {
+        "minimax" => create_minimax_player(argument.map_or(3, parse_number), thread_count),
+        "minimax-race" => Box::new(MinimaxPlayer::with_evaluation(
+            argument.map_or(3, parse_number),
+            Evaluation::Race,
+        )),
+        "shortest-path" => Box::new(ShortestPathPlayer::new(parse_move_chance(argument))),
+        "random" => Box::new(RandomPlayer::new(parse_move_chance(argument))),
+        "engine" => {
+            let command: Vec<String> = argument.unwrap_or("").split_whitespace().map(String::from).collect();
+            match ExternalPlayer::new(&command, timeout) {
+                Ok(player) => Box::new(player),
+                Err(error) => {
+                    eprintln!("{}", error);
+                    process::exit(1);
+                }
+            }
+        }
+        _ => {
+            eprintln!("Unknown player: {}", spec);
+            process::exit(1);
+        }
+    }
+}
+
+// Builds an opening book from self-play games between the minimax and shortest path players. Only
+// the winning side's actions are added so that book weights reflect how often each action won.
+// Games are drawn under the default draw rules, and drawn games add nothing.
+//
+// Usage: quoridor build-book <output path> [game count] [minimax depth] [max ply]
+fn build_book(args: &[String]) {
+    if args.is_empty() {
+        eprintln!("Usage: quoridor build-book <output path> [game count] [minimax depth] [max ply]");
+        process::exit(1);
+    }
+    let path = &args[0];
+    let game_count = parse_arg(args, 1, 100);
+    let branch_depth = parse_arg(args, 2, 2);
+    let max_ply = parse_arg(args, 3, 8);
+
+    let mut opening_book = OpeningBook::new();
+    for i in 0..game_count {
+        let minimax_index = i % 2;
+        let mut board_state = BoardState::new();
+        let mut actions = Vec::<Action>::new();
+        let mut player_index = 0;
+        let mut history = GameHistory::new(DrawRules::default(), &board_state, player_index);
+        let result = loop {
+            let action = if player_index == minimax_index {
+                MinimaxPlayer::take_action(&board_state, player_index, branch_depth).unwrap_or_else(|error| {
+                    eprintln!("{}", error);
+                    process::exit(1);
+                })
+            } else {
+                ShortestPathPlayer::take_action(&board_state, player_index, 0.5)
+            };
+            action.apply(&mut board_state, player_index);
+            actions.push(action);
+            player_index = 1 - player_index;
+            history.record(&board_state, player_index);
+            if let Some(result) = history.get_result(&board_state) {
+                break result;
+            }
+        };
+        // Drawn games have no winner whose actions could be added.
+        match result {
+            GameResult::Win(winner) => {
+                opening_book.add_game(&actions, Some(winner), max_ply);
+                println!("Completed {} of {} Games", i + 1, game_count);
+            }
+            GameResult::Draw => println!("Completed {} of {} Games (drawn)", i + 1, game_count),
+        }
+    }
+
+    if let Err(error) = fs::write(path, opening_book.to_bytes()) {
+        eprintln!("Failed to write opening book to {}: {}", path, error);
+        process::exit(1);
+    }
+    println!("Wrote {} positions to {}", opening_book.len(), path);
+}
+
+// Counts the legal action sequences of the given length, optionally broken down by first action.
+//
+// Usage: quoridor perft <depth> [--position <position>] [--divide]
+fn run_perft(args: &[String]) {
+    if args.is_empty() {
+        eprintln!("Usage: quoridor perft <depth> [--position <position>] [--divide]");
+        process::exit(1);
+    }
+    let depth = parse_number(&args[0]);
+    let (board_state, player_index) = match get_option(args, "--position") {
+        Some(text) => parse_position(&text).unwrap_or_else(|error| {
+            eprintln!("{}", error);
+            process::exit(1);
+        }),
+        None => (BoardState::new(), 0),
+    };
+
+    let start = Instant::now();
+    let node_count = if args.iter().any(|arg| arg == "--divide") {
+        let mut divide = perft::perft_divide(&board_state, player_index, depth);
+        divide.sort_by_key(|(action, _)| format_action(action));
+        for (action, count) in divide.iter() {
+            println!("{}: {}", format_action(action), count);
+        }
+        divide.iter().map(|(_, count)| count).sum()
+    } else {
+        perft::perft(&board_state, player_index, depth)
+    };
+    let duration = start.elapsed().as_secs_f32();
+    println!("Nodes: {}", node_count);
+    println!("Time Elapsed: {:.2} s", duration);
+    println!("Node rate: {:.1} n/s", node_count as f32 / duration);
+}
+
+// Searches a position to the given depth twice, copying the board for every node and then making
+// and unmaking actions on one board, and compares how many nodes each visits a second.
+//
+// Usage: quoridor bench-search <depth> [--position <position>]
+fn run_search_benchmark(args: &[String]) {
+    if args.is_empty() {
+        eprintln!("Usage: quoridor bench-search <depth> [--position <position>]");
+        process::exit(1);
+    }
+    let depth = parse_number(&args[0]);
+    let (board_state, player_index) = match get_option(args, "--position") {
+        Some(text) => parse_position(&text).unwrap_or_else(|error| {
+            eprintln!("{}", error);
+            process::exit(1);
+        }),
+        None => (BoardState::new(), 0),
+    };
+
+    let start = Instant::now();
+    let (copy_score, copy_nodes) =
+        search_benchmark::search_with_copies(&board_state, player_index, depth, 0, -INFINITE_SCORE, INFINITE_SCORE);
+    let copy_duration = start.elapsed().as_secs_f64();
+
+    let mut undo_board_state = board_state;
+    let mut undo_stack = WallUndoStack::default();
+    let start = Instant::now();
+    let (undo_score, undo_nodes) = search_benchmark::search_with_undo(
+        &mut undo_board_state,
+        player_index,
+        depth,
+        0,
+        -INFINITE_SCORE,
+        INFINITE_SCORE,
+        &mut undo_stack,
+    );
+    let undo_duration = start.elapsed().as_secs_f64();
+
+    if (copy_score, copy_nodes) != (undo_score, undo_nodes) {
+        eprintln!("The searches disagree, so the comparison is meaningless");
+        process::exit(1);
+    }
+    println!("Nodes: {}", copy_nodes);
+    println!(
+        "Copying the board: {:.2} s ({})",
+        copy_duration,
+        format_node_rate(copy_nodes, copy_duration)
+    );
+    println!(
+        "Making and unmaking: {:.2} s ({})",
+        undo_duration,
+        format_node_rate(undo_nodes, undo_duration)
+    );
+    if copy_duration > 0.0 && undo_duration > 0.0 {
+        println!("Speedup: {:.2}x", copy_duration / undo_duration);
+    }
+}
+
+// Lists the best actions for the player to move with their scores and principal variations.
+//
+// Usage: quoridor analyze <depth> [--position <position>] [--top <count>]
+fn run_analysis(args: &[String]) {
+    if args.is_empty() {
+        eprintln!("Usage: quoridor analyze <depth> [--position <position>] [--top <count>]");
+        process::exit(1);
+    }
+    let depth = parse_number(&args[0]);
+    let top_count = get_option(args, "--top").map_or(5, |arg| parse_number(&arg));
+    let (board_state, player_index) = match get_option(args, "--position") {
+        Some(text) => parse_position(&text).unwrap_or_else(|error| {
+            eprintln!("{}", error);
+            process::exit(1);
+        }),
+        None => (BoardState::new(), 0),
+    };
+
+    let start = Instant::now();
+    let analysis = MinimaxPlayer::analyze(&board_state, player_index, depth, top_count, false);
+    for (i, action_score) in analysis.top_actions.iter().enumerate() {
+        let line: Vec<String> = action_score.principal_variation.iter().map(format_action).collect();
+        println!(
+            "{}. {} (score {}): {}",
+            i + 1,
+            format_action(&action_score.action),
+            format_score(action_score.score),
+            line.join(" ")
+        );
+    }
+    println!("Nodes: {}", analysis.nodes);
+    println!("Time Elapsed: {:.2} s", start.elapsed().as_secs_f32());
+}
+
+#[cfg(feature = "parallel")]
+fn create_minimax_player(branch_depth: usize, thread_count: usize) -> Box<dyn Player> {
+    if thread_count > 1 {
+        Box::new(parallel_search::ParallelMinimaxPlayer::new(branch_depth, thread_count))
+    } else {
+        Box::new(MinimaxPlayer::new(branch_depth))
+    }
+}
+
+#[cfg(not(feature = "parallel"))]
+fn create_minimax_player(branch_depth: usize, thread_count: usize) -> Box<dyn Player> {
+    if thread_count > 1 {
+        eprintln!("Searching on several threads needs a build with the parallel feature");
+        process::exit(1);
+    }
+    Box::new(MinimaxPlayer::new(branch_depth))
+}
+
+fn load_opening_book(path: &str) -> OpeningBook {
+    let result = fs::read(path)
+        .map_err(|error| error.to_string())
+        .and_then(|bytes| OpeningBook::from_bytes(&bytes));
+    match result {
+        Ok(book) => book,
+        Err(error) => {
+            eprintln!("Failed to load opening book from {}: {}", path, error);
+            process::exit(1);
+        }
+    }
+}
+
+// Searches too short to time have no meaningful rate.
+fn format_node_rate(nodes: usize, seconds: f64) -> String {
+    if seconds > 0.0 {
+        format!("{:.0} n/s", nodes as f64 / seconds)
+    } else {
+        String::from("- n/s")
+    }
+}
+
+fn get_option(args: &[String], name: &str) -> Option<String> {
+    let index = args.iter().position(|arg| arg == name)?;
+    args.get(index + 1).cloned()
+}
+
+fn parse_arg(args: &[String], index: usize, default: usize) -> usize {
+    args.get(index).map_or(default, |arg| parse_number(arg))
+}
+
+fn parse_number(arg: &str) -> usize {
+    arg.parse().unwrap_or_else(|_| {
+        eprintln!("Invalid number: {}", arg);
+        process::exit(1);
+    })
 }
@@ -1,91 +1,910 @@
 use crate::action::Action;
-use crate::board_state::BoardState;
+use crate::board_state::{BoardState, WallUndoStack};
+use crate::endgame_solver::{EndgameResult, EndgameSolver};
+use crate::notation::format_action;
+use crate::player::{check_can_act, Player};
+use crate::race::get_race_distances;
 use crate::validation::*;
 
-use std::f32;
+use serde::Serialize;
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tsify::Tsify;
+// The standard `Instant` panics in the browser, so a version that reads the JS clock there is used.
+use web_time::Instant;
 
-pub struct MinimaxPlayer {}
+// How many nodes are visited between checks of the time limit.
+const NODES_PER_LIMIT_CHECK: usize = 1024;
+
+// Scores are from the scoring player's side. A game in progress scores the difference between the
+// players' distances to their goals, and a finished game scores WIN_SCORE less the number of plies
+// it took to finish, negated for a loss. Sooner wins therefore score higher than later ones, and
+// later losses score higher than sooner ones.
+pub const WIN_SCORE: i32 = 1_000_000;
+// No search gets anywhere near this many plies deep, so scores beyond this are finished games.
+const MAX_RESULT_PLIES: i32 = 1000;
+// Beyond any score, for the bounds of a full search window. It can be negated without overflow.
+pub const INFINITE_SCORE: i32 = i32::MAX;
+// How far either side of the previous iteration's score the next iteration's window reaches at
+// first. Scores change by a step or two from one depth to the next while the players race.
+pub const ASPIRATION_WINDOW: i32 = 2;
+
+// How the search scores the positions at its leaves.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Evaluation {
+    // The difference between the players' distances to their goals.
+    Distance,
+    // The difference between the players' race distances, which also count the moves the pawns
+    // gain or lose getting past each other. It takes several times longer to work out.
+    Race,
+}
+
+pub struct MinimaxPlayer {
+    pub evaluation: Evaluation,
+    pub branch_depth: usize,
+    last_report: Option<SearchReport>,
+}
+
+// What a search found and how much work it took, so that what the bot is thinking can be shown.
+#[derive(Clone, Debug, PartialEq, Serialize, Tsify)]
+pub struct SearchReport {
+    pub best_action: Action,
+    pub score: i32,
+    // Whether the score is a forced win or loss, and in how many plies.
+    pub result: Option<EndgameResult>,
+    pub principal_variation: Vec<Action>,
+    pub depth: usize,
+    pub nodes: usize,
+    pub cutoffs: usize,
+    pub elapsed_ms: f64,
+    // Positions without walls left are solved exactly instead of searched. The principal variation
+    // is then the perfect line to the end of the game and the score is the one at its end.
+    pub is_solved: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Tsify)]
+pub struct ActionScore {
+    pub action: Action,
+    pub score: i32,
+    pub principal_variation: Vec<Action>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Tsify)]
+pub struct Analysis {
+    pub depth: usize,
+    pub nodes: usize,
+    pub top_actions: Vec<ActionScore>,
+    // The score of each legal wall indexed by x, y and orientation (0 for horizontal, 1 for
+    // vertical, as `apply_block_action` takes them), or null where a wall can't be placed.
+    #[tsify(type = "(number | null)[][][] | null")]
+    pub wall_scores: Option<[[[Option<i32>; 2]; 8]; 8]>,
+}
 
 impl MinimaxPlayer {
-    pub fn take_action(board_state: &BoardState, player_index: usize, branch_depth: usize) -> Action {
-        let mut node = MinimaxBoardNode::new(board_state, player_index);
-        let _turn_play_count = node.build_children(branch_depth, player_index, true, f32::MIN, f32::MAX);
-        return node.best_action.unwrap();
+    // A search needs at least one ply to find an action to take.
+    pub fn new(branch_depth: usize) -> Self {
+        MinimaxPlayer {
+            evaluation: Evaluation::Distance,
+            branch_depth: branch_depth.max(1),
+            last_report: None,
+        }
+    }
+
+    pub fn with_evaluation(branch_depth: usize, evaluation: Evaluation) -> Self {
+        MinimaxPlayer {
+            evaluation,
+            ..MinimaxPlayer::new(branch_depth)
+        }
+    }
+
+    pub fn take_action(board_state: &BoardState, player_index: usize, branch_depth: usize) -> Result<Action, String> {
+        MinimaxPlayer::take_action_with_report(board_state, player_index, branch_depth).map(|report| report.best_action)
+    }
+
+    pub fn take_action_with_report(
+        board_state: &BoardState,
+        player_index: usize,
+        branch_depth: usize,
+    ) -> Result<SearchReport, String> {
+        MinimaxPlayer::take_action_with_evaluation(board_state, player_index, branch_depth, Evaluation::Distance)
+    }
+
+    pub fn take_action_with_evaluation(
+        board_state: &BoardState,
+        player_index: usize,
+        branch_depth: usize,
+        evaluation: Evaluation,
+    ) -> Result<SearchReport, String> {
+        check_can_act(board_state, player_index)?;
+        let start = Instant::now();
+        if let Some(report) = MinimaxPlayer::solve_with_report(board_state, player_index) {
+            return Ok(report);
+        }
+        let branch_depth = branch_depth.max(1);
+        let mut context = SearchContext::new(None, None);
+        context.evaluation = evaluation;
+        let mut board_state = *board_state;
+        let mut node = MinimaxBoardNode::new(&mut board_state, player_index, 0);
+        let nodes = node.build_children(branch_depth, -INFINITE_SCORE, INFINITE_SCORE, &mut context);
+        let best_action = node
+            .best_action
+            .ok_or_else(|| format!("Player {} has no legal actions", player_index + 1))?;
+        Ok(SearchReport {
+            best_action,
+            score: node.score,
+            result: get_forced_result(node.score),
+            principal_variation: node.principal_variation,
+            depth: branch_depth,
+            nodes,
+            cutoffs: context.cutoffs,
+            elapsed_ms: start.elapsed().as_secs_f64() * 1000.0,
+            is_solved: false,
+        })
+    }
+
+    // Positions without walls left are solved exactly instead of searched.
+    pub fn solve_with_report(board_state: &BoardState, player_index: usize) -> Option<SearchReport> {
+        let start = Instant::now();
+        let solution = EndgameSolver::solve(board_state, player_index)?;
+        let best_action = *solution.line.first()?;
+        Some(SearchReport {
+            best_action,
+            score: score_result(solution.result),
+            result: Some(solution.result),
+            depth: solution.line.len(),
+            principal_variation: solution.line,
+            nodes: 0,
+            cutoffs: 0,
+            elapsed_ms: start.elapsed().as_secs_f64() * 1000.0,
+            is_solved: true,
+        })
+    }
+
+    // Scores every legal action for the player to move, best first. Each action is searched with
+    // a full window, so the scores of the actions after the best one are exact rather than bounds,
+    // which makes this much slower than a normal search to the same depth.
+    pub fn analyze(
+        board_state: &BoardState,
+        player_index: usize,
+        depth: usize,
+        top_count: usize,
+        include_wall_scores: bool,
+    ) -> Analysis {
+        let depth = depth.max(1);
+        let mut context = SearchContext::new(None, None);
+        let mut action_scores: Vec<ActionScore> = get_legal_actions(board_state, player_index)
+            .into_iter()
+            .map(|action| {
+                let mut new_board_state = board_state.from_action(&action, player_index);
+                let mut node = MinimaxBoardNode::new(&mut new_board_state, 1 - player_index, 1);
+                node.build_children(depth - 1, -INFINITE_SCORE, INFINITE_SCORE, &mut context);
+                let mut principal_variation = vec![action];
+                principal_variation.append(&mut node.principal_variation);
+                ActionScore {
+                    action,
+                    score: -node.score,
+                    principal_variation,
+                }
+            })
+            .collect();
+
+        let wall_scores = if include_wall_scores {
+            let mut wall_scores = [[[None; 2]; 8]; 8];
+            for action_score in action_scores.iter() {
+                if let Action::Block(position, orientation) = action_score.action {
+                    wall_scores[position.x as usize][position.y as usize][orientation.get_api_index()] =
+                        Some(action_score.score);
+                }
+            }
+            Some(wall_scores)
+        } else {
+            None
+        };
+
+        // The sort is stable, so ties keep the order actions are searched in and the first action
+        // matches the one `take_action` would choose.
+        action_scores.sort_by_key(|action_score| -action_score.score);
+        action_scores.truncate(top_count);
+        Analysis {
+            depth,
+            nodes: context.nodes,
+            top_actions: action_scores,
+            wall_scores,
+        }
+    }
+}
+
+impl Player for MinimaxPlayer {
+    fn get_name(&self) -> String {
+        match self.evaluation {
+            Evaluation::Distance => format!("Minimax {}", self.branch_depth),
+            Evaluation::Race => format!("Minimax {} (race)", self.branch_depth),
+        }
+    }
+
+    fn choose_action(&mut self, board_state: &BoardState, player_index: usize) -> Result<Action, String> {
+        let report =
+            MinimaxPlayer::take_action_with_evaluation(board_state, player_index, self.branch_depth, self.evaluation)?;
+        let action = report.best_action;
+        self.last_report = Some(report);
+        Ok(action)
+    }
+
+    fn get_last_report(&self) -> Option<&SearchReport> {
+        self.last_report.as_ref()
+    }
+}
+
+impl fmt::Display for SearchReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let line: Vec<String> = self.principal_variation.iter().map(format_action).collect();
+        write!(
+            f,
+            "depth {} score {} nodes {} cutoffs {} time {:.1} ms pv {}",
+            self.depth,
+            format_score(self.score),
+            self.nodes,
+            self.cutoffs,
+            self.elapsed_ms,
+            line.join(" ")
+        )?;
+        if self.is_solved {
+            write!(f, " (solved)")?;
+        }
+        Ok(())
     }
 }
 
-struct MinimaxBoardNode<'a> {
-    pub board_state: &'a BoardState,
+// Iterative deepening searches each depth after the first in a narrow window around the previous
+// iteration's score, which cuts off more. When the score falls outside the window, the side it fell
+// out of is opened and the depth is searched again.
+pub fn get_aspiration_window(depth: usize, previous_score: i32) -> (i32, i32) {
+    if depth <= 1 {
+        (-INFINITE_SCORE, INFINITE_SCORE)
+    } else {
+        (previous_score - ASPIRATION_WINDOW, previous_score + ASPIRATION_WINDOW)
+    }
+}
+
+// Pairs each action with its index in generated order and sorts them best first by the score of
+// the position they lead to, from the side of the player making them. Actions that would trap a
+// player are left out.
+pub fn order_actions(
+    board_state: &mut BoardState,
+    player_index: usize,
+    ply: usize,
+    actions: Vec<Action>,
+    evaluation: Evaluation,
+    undo_stack: &mut WallUndoStack,
+) -> Vec<(usize, Action)> {
+    let mut scored_actions: Vec<(i32, usize, Action)> = actions
+        .into_iter()
+        .enumerate()
+        .filter_map(|(index, action)| {
+            let undo_record = action.make(board_state, player_index, undo_stack);
+            let score = if is_either_player_trapped(board_state) {
+                None
+            } else {
+                Some(-evaluate_with(evaluation, board_state, 1 - player_index, ply + 1))
+            };
+            undo_record.unmake(board_state, undo_stack);
+            score.map(|score| (score, index, action))
+        })
+        .collect();
+    // The sort is stable, so equal scores stay in generated order.
+    scored_actions.sort_by_key(|&(score, _, _)| -score);
+    scored_actions
+        .into_iter()
+        .map(|(_, index, action)| (index, action))
+        .collect()
+}
+
+// Pairs each action with its index in generated order, with the killer action moved to the front
+// when it's one of them. An action that refuted one position often refutes its siblings too.
+pub fn order_killer_first(actions: Vec<Action>, killer_action: Option<Action>) -> Vec<(usize, Action)> {
+    let mut ordered_actions: Vec<(usize, Action)> = actions.into_iter().enumerate().collect();
+    if let Some(killer_index) = ordered_actions
+        .iter()
+        .position(|&(_, action)| Some(action) == killer_action)
+    {
+        let killer = ordered_actions.remove(killer_index);
+        ordered_actions.insert(0, killer);
+    }
+    ordered_actions
+}
+
+pub fn set_killer_action(killer_actions: &mut Vec<Option<Action>>, ply: usize, action: Action) {
+    if killer_actions.len() <= ply {
+        killer_actions.resize(ply + 1, None);
+    }
+    killer_actions[ply] = Some(action);
+}
+
+// Searching in a different order mustn't change which of several equally good actions is chosen,
+// so the action generated first wins a tie, as it would if the actions were searched in generated
+// order.
+pub fn is_better_child(score: i32, index: usize, best_score: i32, best_index: usize) -> bool {
+    score > best_score || (score == best_score && index < best_index)
+}
+
+// The bound below which an action can't replace the best so far. An action generated before the
+// best one only has to tie it, so it's searched just below the best score.
+pub fn get_null_window_threshold(
+    index: usize,
+    best_index: usize,
+    best_score: i32,
+    original_alpha: i32,
+    alpha: i32,
+) -> i32 {
+    if index < best_index && best_score > original_alpha {
+        alpha - 1
+    } else {
+        alpha
+    }
+}
+
+// Scores a position reached after the given number of plies from the root of a search. The race
+// evaluation depends on who moves next, which it takes to be the scoring player. Either way the
+// score for one player is the negation of the score for the other.
+pub fn evaluate_with(evaluation: Evaluation, board_state: &BoardState, scoring_player: usize, ply: usize) -> i32 {
+    let player_distance = board_state.get_player_distance(scoring_player);
+    let opp_distance = board_state.get_player_distance(1 - scoring_player);
+    if player_distance == 0 {
+        WIN_SCORE - ply as i32
+    } else if opp_distance == 0 {
+        -(WIN_SCORE - ply as i32)
+    } else {
+        match evaluation {
+            Evaluation::Distance => (opp_distance - player_distance) as i32,
+            Evaluation::Race => {
+                let race_distances = get_race_distances(board_state, scoring_player);
+                (race_distances[1 - scoring_player] - race_distances[scoring_player]) as i32
+            }
+        }
+    }
+}
+
+pub fn score_result(result: EndgameResult) -> i32 {
+    match result {
+        EndgameResult::Win(plies) => WIN_SCORE - plies as i32,
+        EndgameResult::Loss(plies) => -(WIN_SCORE - plies as i32),
+        EndgameResult::Draw => 0,
+    }
+}
+
+pub fn get_forced_result(score: i32) -> Option<EndgameResult> {
+    if score >= WIN_SCORE - MAX_RESULT_PLIES {
+        Some(EndgameResult::Win((WIN_SCORE - score) as usize))
+    } else if score <= -(WIN_SCORE - MAX_RESULT_PLIES) {
+        Some(EndgameResult::Loss((WIN_SCORE + score) as usize))
+    } else {
+        None
+    }
+}
+
+// Forced results are written as "win <plies>" or "loss <plies>", like the endgame solver's.
+pub fn format_score(score: i32) -> String {
+    match get_forced_result(score) {
+        Some(EndgameResult::Win(plies)) => format!("win {}", plies),
+        Some(EndgameResult::Loss(plies)) => format!("loss {}", plies),
+        _ => score.to_string(),
+    }
+}
+
+pub struct SearchContext {
+    pub nodes: usize,
+    pub cutoffs: usize,
+    deadline: Option<Instant>,
+    stop: Option<Arc<AtomicBool>>,
+    pub aborted: bool,
+    pub undo_stack: WallUndoStack,
+    pub evaluation: Evaluation,
+    // The last action to cause a cutoff at each ply.
+    killer_actions: Vec<Option<Action>>,
+}
+
+impl SearchContext {
+    pub fn new(deadline: Option<Instant>, stop: Option<Arc<AtomicBool>>) -> Self {
+        SearchContext {
+            nodes: 0,
+            cutoffs: 0,
+            deadline,
+            stop,
+            aborted: false,
+            undo_stack: WallUndoStack::default(),
+            evaluation: Evaluation::Distance,
+            killer_actions: Vec::new(),
+        }
+    }
+
+    fn should_abort(&mut self) -> bool {
+        if !self.aborted {
+            let is_stopped = self.stop.as_ref().is_some_and(|stop| stop.load(Ordering::Relaxed));
+            // Reading the clock is comparatively slow so it is only checked periodically.
+            let is_out_of_time = self.nodes.is_multiple_of(NODES_PER_LIMIT_CHECK)
+                && self.deadline.is_some_and(|deadline| Instant::now() >= deadline);
+            self.aborted = is_stopped || is_out_of_time;
+        }
+        self.aborted
+    }
+}
+
+// Nodes share one board, which each child changes with `Action::make` and puts back with
+// `UndoRecord::unmake` before its parent moves on to the next action.
+pub struct MinimaxBoardNode<'a> {
+    pub board_state: &'a mut BoardState,
     pub player_index: usize,
+    pub ply: usize,
     pub best_action: Option<Action>,
-    pub score: f32,
+    pub score: i32,
+    pub principal_variation: Vec<Action>,
 }
 
 impl<'a> MinimaxBoardNode<'a> {
-    pub fn new(board_state: &'a BoardState, player_index: usize) -> Self {
+    pub fn new(board_state: &'a mut BoardState, player_index: usize, ply: usize) -> Self {
         MinimaxBoardNode {
             board_state: board_state,
             player_index: player_index,
+            ply,
             best_action: None,
-            score: 0.0,
+            score: 0,
+            principal_variation: Vec::new(),
         }
     }
 
-    pub fn build_children(
-        &mut self,
-        branch_depth: usize,
-        scoring_player: usize,
-        maximizing: bool,
-        alpha: f32,
-        beta: f32,
-    ) -> usize {
-        let opp_index = 1 - scoring_player;
-        let opp_distance = self.board_state.get_player_distance(opp_index);
-        let player_distance = self.board_state.get_player_distance(scoring_player);
+    // Negamax with principal variation search, so the score is from the side of the player to
+    // move. The first action is searched with the full window and the rest with a null window,
+    // which only shows whether they beat the best so far. The few that do are searched again
+    // with the full window. Scores that fall outside the window are only bounds.
+    pub fn build_children(&mut self, branch_depth: usize, alpha: i32, beta: i32, context: &mut SearchContext) -> usize {
+        context.nodes += 1;
+        if context.should_abort() {
+            // The result of an aborted search is discarded, so there's no need to score the node.
+            return 1;
+        }
+        let opp_distance = self.board_state.get_player_distance(1 - self.player_index);
+        let player_distance = self.board_state.get_player_distance(self.player_index);
         if player_distance == 0 || opp_distance == 0 || branch_depth == 0 {
             // When the board has no children calculate the distances from the end for each player.
-            self.score = (opp_distance - player_distance) as f32;
+            self.score = evaluate_with(context.evaluation, self.board_state, self.player_index, self.ply);
             return 1;
         }
 
         let mut valid_actions = Vec::<Action>::new();
         valid_actions.append(&mut get_valid_move_actions(&self.board_state, self.player_index));
         valid_actions.append(&mut get_valid_block_actions(&self.board_state, self.player_index));
+        // Above the last ply, searching the most promising actions first lets the null window
+        // searches of the rest cut off sooner.
+        let ordered_actions = if branch_depth > 1 && alpha + 1 < beta {
+            order_actions(
+                self.board_state,
+                self.player_index,
+                self.ply,
+                valid_actions,
+                context.evaluation,
+                &mut context.undo_stack,
+            )
+        } else {
+            let killer_action = context.killer_actions.get(self.ply).copied().flatten();
+            order_killer_first(valid_actions, killer_action)
+        };
 
-        let mut a = alpha;
-        let mut b = beta;
-        let mut score = if maximizing { f32::MIN } else { f32::MAX };
-        let mut best_action_index = 0;
+        let original_alpha = alpha;
+        let mut alpha = alpha;
+        let mut score = -INFINITE_SCORE;
+        let mut best_action_index = usize::MAX;
         let mut turn_play_count = 1;
-        for (i, &action) in valid_actions.iter().enumerate() {
-            let mut new_board_state = self.board_state.from_action(&action, self.player_index);
-            if !is_either_player_trapped(&new_board_state) {
-                let mut child_node = MinimaxBoardNode::new(&new_board_state, 1 - self.player_index);
-                turn_play_count += child_node.build_children(branch_depth - 1, scoring_player, !maximizing, a, b);
-                if maximizing {
-                    if child_node.score > score {
-                        score = child_node.score;
-                        best_action_index = i;
-                    }
-                    a = f32::max(a, score);
-                    if a >= b {
-                        break;
-                    }
+        for &(index, action) in ordered_actions.iter() {
+            if context.aborted {
+                break;
+            }
+            let undo_record = action.make(self.board_state, self.player_index, &mut context.undo_stack);
+            if is_either_player_trapped(self.board_state) {
+                undo_record.unmake(self.board_state, &mut context.undo_stack);
+                continue;
+            }
+            let (child_score, mut child_principal_variation) = if best_action_index == usize::MAX {
+                self.search_child(branch_depth, alpha, beta, context, &mut turn_play_count)
+            } else {
+                let threshold = get_null_window_threshold(index, best_action_index, score, original_alpha, alpha);
+                let (child_score, child_principal_variation) =
+                    self.search_child(branch_depth, threshold, threshold + 1, context, &mut turn_play_count);
+                // A child that fails low has searched all of its children. When they are leaves it
+                // has scored them exactly, so only children further from the leaves need searching
+                // again with the full window.
+                if branch_depth > 2 && child_score > threshold && child_score < beta {
+                    self.search_child(branch_depth, threshold, beta, context, &mut turn_play_count)
                 } else {
-                    if child_node.score < score {
-                        score = child_node.score;
-                        best_action_index = i;
-                    }
-                    b = f32::min(b, score);
-                    if a >= b {
-                        break;
-                    }
+                    (child_score, child_principal_variation)
                 }
+            };
+            undo_record.unmake(self.board_state, &mut context.undo_stack);
+
+            if is_better_child(child_score, index, score, best_action_index) {
+                score = child_score;
+                best_action_index = index;
+                self.principal_variation = vec![action];
+                self.principal_variation.append(&mut child_principal_variation);
+            }
+            alpha = alpha.max(score);
+            if alpha >= beta {
+                context.cutoffs += 1;
+                set_killer_action(&mut context.killer_actions, self.ply, action);
+                break;
             }
         }
 
-        self.best_action = Option::from(valid_actions[best_action_index]);
+        let best_action = ordered_actions.iter().find(|&&(index, _)| index == best_action_index);
+        self.best_action = best_action
+            .or_else(|| ordered_actions.first())
+            .map(|&(_, action)| action);
         self.score = score;
         return turn_play_count;
     }
+
+    // Searches the position the last action made with the given window from this node's side,
+    // and returns the score from this node's side along with the child's principal variation.
+    fn search_child(
+        &mut self,
+        branch_depth: usize,
+        alpha: i32,
+        beta: i32,
+        context: &mut SearchContext,
+        turn_play_count: &mut usize,
+    ) -> (i32, Vec<Action>) {
+        let mut child_node = MinimaxBoardNode::new(self.board_state, 1 - self.player_index, self.ply + 1);
+        *turn_play_count += child_node.build_children(branch_depth - 1, -beta, -alpha, context);
+        (-child_node.score, child_node.principal_variation)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::iterative_search::SearchLimits;
+    use crate::notation::parse_position;
+    use crate::vector2::Vector2;
+
+    fn no_limits(max_depth: usize) -> SearchLimits {
+        SearchLimits {
+            max_depth,
+            time_limit: None,
+            stop: None,
+        }
+    }
+
+    // The plain alpha-beta minimax the search used to be, which gives the results it has to keep.
+    // Returns the score from the scoring player's side, the principal variation and the nodes.
+    fn reference_search(
+        board_state: &BoardState,
+        player_index: usize,
+        scoring_player: usize,
+        depth: usize,
+        ply: usize,
+        mut alpha: i32,
+        mut beta: i32,
+    ) -> (i32, Vec<Action>, usize) {
+        let is_finished = board_state.get_player_distance(0) == 0 || board_state.get_player_distance(1) == 0;
+        if is_finished || depth == 0 {
+            return (
+                evaluate_with(Evaluation::Distance, board_state, scoring_player, ply),
+                Vec::new(),
+                1,
+            );
+        }
+        let maximizing = player_index == scoring_player;
+        let mut score = if maximizing { i32::MIN } else { i32::MAX };
+        let mut principal_variation = Vec::new();
+        let mut nodes = 1;
+        let mut actions = get_valid_move_actions(board_state, player_index);
+        actions.append(&mut get_valid_block_actions(board_state, player_index));
+        for action in actions {
+            let new_board_state = board_state.from_action(&action, player_index);
+            if is_either_player_trapped(&new_board_state) {
+                continue;
+            }
+            let (child_score, mut child_principal_variation, child_nodes) = reference_search(
+                &new_board_state,
+                1 - player_index,
+                scoring_player,
+                depth - 1,
+                ply + 1,
+                alpha,
+                beta,
+            );
+            nodes += child_nodes;
+            if (maximizing && child_score > score) || (!maximizing && child_score < score) {
+                score = child_score;
+                principal_variation = vec![action];
+                principal_variation.append(&mut child_principal_variation);
+            }
+            if maximizing {
+                alpha = alpha.max(score);
+            } else {
+                beta = beta.min(score);
+            }
+            if alpha >= beta {
+                break;
+            }
+        }
+        (score, principal_variation, nodes)
+    }
+
+    #[test]
+    fn search_matches_plain_alpha_beta() {
+        let positions = [
+            ("-.e1e9.10.10.1", 2),
+            ("d4hE6V.e3e7.9.9.1", 3),
+            ("c7hf7hD2VG3V.d5e6.8.8.2", 2),
+            ("-.e8e5.10.10.1", 2),
+            ("a3hc3he3hg3v.e2e8.6.10.2", 2),
+            ("d7hf7hD5VF5VH8H.e6e4.6.7.1", 3),
+            ("e2hc6vE7HG4V.d3f7.8.8.2", 2),
+            ("a6hc6he6hg6hb2vD2VF2VH3V.e4e6.5.7.1", 2),
+        ];
+        let mut total_nodes = 0;
+        let mut total_reference_nodes = 0;
+        for &(position, depth) in positions.iter() {
+            let (board_state, player_index) = parse_position(position).unwrap();
+            let (score, principal_variation, reference_nodes) =
+                reference_search(&board_state, player_index, player_index, depth, 0, i32::MIN, i32::MAX);
+
+            let report = MinimaxPlayer::take_action_with_report(&board_state, player_index, depth).unwrap();
+            let info = MinimaxPlayer::search(&board_state, player_index, &no_limits(depth), &mut |_| {});
+
+            assert_eq!(score, report.score, "{}", position);
+            assert_eq!(principal_variation, report.principal_variation, "{}", position);
+            assert_eq!(score, info.score, "{}", position);
+            assert_eq!(principal_variation, info.principal_variation, "{}", position);
+            total_nodes += report.nodes;
+            total_reference_nodes += reference_nodes;
+        }
+        // Shallow searches gain little and can lose a few nodes to the searches that keep ties
+        // resolved as before, so the saving is measured over the whole set.
+        assert_eq!(
+            true,
+            total_nodes * 2 < total_reference_nodes,
+            "{} nodes, {} with plain alpha-beta",
+            total_nodes,
+            total_reference_nodes
+        );
+    }
+
+    #[test]
+    fn search_matches_take_action() {
+        let board_state = BoardState::new();
+
+        let info = MinimaxPlayer::search(&board_state, 0, &no_limits(2), &mut |_| {});
+
+        assert_eq!(2, info.depth);
+        assert_eq!(
+            MinimaxPlayer::take_action(&board_state, 0, 2).unwrap(),
+            info.principal_variation[0]
+        );
+    }
+
+    #[test]
+    fn analyze_matches_search() {
+        let board_state = BoardState::new();
+        let info = MinimaxPlayer::search(&board_state, 0, &no_limits(2), &mut |_| {});
+
+        let analysis = MinimaxPlayer::analyze(&board_state, 0, 2, 5, false);
+
+        assert_eq!(5, analysis.top_actions.len());
+        assert_eq!(info.principal_variation[0], analysis.top_actions[0].action);
+        assert_eq!(info.score, analysis.top_actions[0].score);
+        assert_eq!(None, analysis.wall_scores);
+        for pair in analysis.top_actions.windows(2) {
+            assert_eq!(true, pair[0].score >= pair[1].score);
+        }
+    }
+
+    #[test]
+    fn analyze_scores_every_legal_wall() {
+        let board_state = BoardState::new();
+
+        let analysis = MinimaxPlayer::analyze(&board_state, 0, 1, usize::MAX, true);
+        let wall_scores = analysis.wall_scores.unwrap();
+
+        assert_eq!(131, analysis.top_actions.len());
+        assert_eq!(
+            128,
+            wall_scores
+                .iter()
+                .flatten()
+                .flatten()
+                .filter(|score| score.is_some())
+                .count()
+        );
+        for action_score in analysis.top_actions.iter() {
+            let new_board_state = board_state.from_action(&action_score.action, 0);
+            let score = new_board_state.get_player_distance(1) - new_board_state.get_player_distance(0);
+            assert_eq!(score as i32, action_score.score, "{:?}", action_score.action);
+            if let Action::Block(position, orientation) = action_score.action {
+                assert_eq!(
+                    Some(score as i32),
+                    wall_scores[position.x as usize][position.y as usize][orientation.get_api_index()]
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn wall_scores_number_orientations_like_wasm_api() {
+        // Only the horizontal wall across the e file below e9 gets in player 1's way, and the wasm
+        // API numbers it 0.
+        let (board_state, player_index) = parse_position("-.e1a9.10.10.1").unwrap();
+
+        let analysis = MinimaxPlayer::analyze(&board_state, player_index, 1, usize::MAX, true);
+
+        let wall_scores = analysis.wall_scores.unwrap();
+        assert_eq!([Some(-1), Some(0)], wall_scores[4][7]);
+    }
+
+    #[test]
+    fn report_counts_search_work() {
+        let board_state = BoardState::new();
+        let info = MinimaxPlayer::search(&board_state, 0, &no_limits(2), &mut |_| {});
+
+        let report = MinimaxPlayer::take_action_with_report(&board_state, 0, 2).unwrap();
+
+        assert_eq!(2, report.depth);
+        assert_eq!(info.principal_variation, report.principal_variation);
+        assert_eq!(info.score, report.score);
+        assert_eq!(report.principal_variation[0], report.best_action);
+        assert_eq!(false, report.is_solved);
+        // Only the first iteration of `search` has a full window like `take_action_with_report`.
+        // It visits the root and its children.
+        let first_iteration = MinimaxPlayer::search(&board_state, 0, &no_limits(1), &mut |_| {});
+        assert_eq!(132, first_iteration.nodes);
+        assert_eq!(
+            132,
+            MinimaxPlayer::take_action_with_report(&board_state, 0, 1)
+                .unwrap()
+                .nodes
+        );
+        assert_eq!(true, report.cutoffs > 0);
+    }
+
+    #[test]
+    fn report_solved_position() {
+        let (board_state, player_index) = parse_position("-.e8a5.0.0.1").unwrap();
+
+        let report = MinimaxPlayer::take_action_with_report(&board_state, player_index, 2).unwrap();
+
+        assert_eq!(true, report.is_solved);
+        assert_eq!(Action::Move(Vector2::new(4, 8)), report.best_action);
+        assert_eq!(vec![Action::Move(Vector2::new(4, 8))], report.principal_variation);
+        assert_eq!(WIN_SCORE - 1, report.score);
+        assert_eq!(Some(EndgameResult::Win(1)), report.result);
+    }
+
+    #[test]
+    fn player_keeps_last_report() {
+        let mut player = MinimaxPlayer::new(1);
+        assert_eq!(None, player.get_last_report());
+
+        let action = player.choose_action(&BoardState::new(), 0).unwrap();
+
+        assert_eq!(Some(action), player.get_last_report().map(|report| report.best_action));
+    }
+
+    #[test]
+    fn player_searches_at_least_one_ply() {
+        let mut player = MinimaxPlayer::new(0);
+
+        let action = player.choose_action(&BoardState::new(), 0).unwrap();
+
+        assert_eq!(1, player.branch_depth);
+        assert_eq!(true, validate_action(&BoardState::new(), 0, &action));
+        assert_eq!(
+            Ok(1),
+            MinimaxPlayer::take_action_with_evaluation(&BoardState::new(), 0, 0, Evaluation::Distance)
+                .map(|report| report.depth)
+        );
+    }
+
+    #[test]
+    fn search_fails_when_game_is_over() {
+        let (board_state, _) = parse_position("-.e9e5.10.10.2").unwrap();
+
+        let report = MinimaxPlayer::take_action_with_evaluation(&board_state, 1, 2, Evaluation::Distance);
+
+        assert_eq!(Some(String::from("The game is over")), report.err());
+    }
+
+    #[test]
+    fn search_takes_fastest_win() {
+        // Placing walls first also wins, but later.
+        let (board_state, player_index) = parse_position("-.e8e5.10.10.1").unwrap();
+
+        let report = MinimaxPlayer::take_action_with_report(&board_state, player_index, 3).unwrap();
+
+        assert_eq!(Action::Move(Vector2::new(4, 8)), report.best_action);
+        assert_eq!(Some(EndgameResult::Win(1)), report.result);
+        assert_eq!("win 1", format_score(report.score));
+    }
+
+    #[test]
+    fn search_reports_forced_loss() {
+        // Without walls nothing stops the second player reaching e1 on their next turn.
+        let (board_state, player_index) = parse_position("-.a1e2.0.0.1").unwrap();
+
+        let info = MinimaxPlayer::search(&board_state, player_index, &no_limits(2), &mut |_| {});
+
+        assert_eq!(Some(EndgameResult::Loss(2)), get_forced_result(info.score));
+        assert_eq!("loss 2", format_score(info.score));
+    }
+
+    #[test]
+    fn evaluate_prefers_sooner_wins_and_later_losses() {
+        let (won, _) = parse_position("-.e9e5.10.10.2").unwrap();
+
+        assert_eq!(
+            true,
+            evaluate_with(Evaluation::Distance, &won, 0, 1) > evaluate_with(Evaluation::Distance, &won, 0, 3)
+        );
+        assert_eq!(
+            true,
+            evaluate_with(Evaluation::Distance, &won, 1, 3) > evaluate_with(Evaluation::Distance, &won, 1, 1)
+        );
+        assert_eq!(0, evaluate_with(Evaluation::Distance, &BoardState::new(), 0, 0));
+        assert_eq!(
+            Some(EndgameResult::Win(1)),
+            get_forced_result(evaluate_with(Evaluation::Distance, &won, 0, 1))
+        );
+        assert_eq!(
+            Some(EndgameResult::Loss(3)),
+            get_forced_result(evaluate_with(Evaluation::Distance, &won, 1, 3))
+        );
+        assert_eq!(None, get_forced_result(8));
+    }
+
+    #[test]
+    fn race_evaluation_counts_jumps() {
+        // Both pawns are five moves from their goals, but whoever moves first walks up to the other
+        // pawn and gets jumped.
+        let (board_state, _) = parse_position("-.e4e6.10.10.1").unwrap();
+
+        assert_eq!(0, evaluate_with(Evaluation::Distance, &board_state, 0, 0));
+        assert_eq!(-1, evaluate_with(Evaluation::Race, &board_state, 0, 0));
+        assert_eq!(-1, evaluate_with(Evaluation::Race, &board_state, 1, 0));
+    }
+
+    #[test]
+    fn race_player_chooses_valid_action() {
+        let (board_state, player_index) = parse_position("d4hE6V.e3e7.9.9.1").unwrap();
+        let mut player = MinimaxPlayer::with_evaluation(2, Evaluation::Race);
+
+        let action = player.choose_action(&board_state, player_index).unwrap();
+
+        assert_eq!(true, validate_action(&board_state, player_index, &action));
+        assert_eq!("Minimax 2 (race)", player.get_name());
+    }
+
+    #[test]
+    fn search_reports_each_iteration() {
+        let mut depths = Vec::new();
+
+        MinimaxPlayer::search(&BoardState::new(), 0, &no_limits(2), &mut |info| {
+            depths.push(info.depth)
+        });
+
+        assert_eq!(vec![1, 2], depths);
+    }
+
+    #[test]
+    fn search_completes_first_iteration_when_stopped() {
+        let limits = SearchLimits {
+            max_depth: 3,
+            time_limit: None,
+            stop: Some(Arc::new(AtomicBool::new(true))),
+        };
+
+        let info = MinimaxPlayer::search(&BoardState::new(), 0, &limits, &mut |_| {});
+
+        assert_eq!(1, info.depth);
+        assert_eq!(1, info.principal_variation.len());
+    }
 }
@@ -0,0 +1,271 @@
+use crate::action::Action;
+use crate::board_state::BoardState;
+use crate::validation::{is_player_trapped, is_wall_overlapping};
+use crate::vector2::Vector2;
+use crate::wall_orientation::WallOrientation;
+
+// Cells are written as a column letter followed by a row number, so the starting cells are e1
+// and e9. Walls are written as the cell at their bottom left corner followed by h or v for their
+// orientation, so d4h separates d4 and e4 from d5 and e5.
+pub fn format_cell(cell: Vector2<isize>) -> String {
+    format!("{}{}", (b'a' + cell.x as u8) as char, cell.y + 1)
+}
+
+pub fn parse_cell(text: &str) -> Option<Vector2<isize>> {
+    let bytes = text.as_bytes();
+    if bytes.len() != 2 || !(b'a'..=b'i').contains(&bytes[0]) || !(b'1'..=b'9').contains(&bytes[1]) {
+        return None;
+    }
+    Some(Vector2::new((bytes[0] - b'a') as isize, (bytes[1] - b'1') as isize))
+}
+
+pub fn format_action(action: &Action) -> String {
+    match action {
+        Action::Move(position) => format_cell(*position),
+        Action::Block(position, orientation) => format!("{}{}", format_cell(*position), orientation_char(*orientation)),
+    }
+}
+
+pub fn parse_action(text: &str) -> Result<Action, String> {
+    let error = || format!("Invalid action: {}", text);
+    if text.len() == 2 {
+        return parse_cell(text).map(Action::Move).ok_or_else(error);
+    }
+    if text.len() != 3 || !text.is_ascii() {
+        return Err(error());
+    }
+    let position = parse_cell(&text[..2]).ok_or_else(error)?;
+    let orientation = parse_orientation(text.as_bytes()[2]).ok_or_else(error)?;
+    if !BoardState::is_wall_index_in_bounds(position) {
+        return Err(error());
+    }
+    Ok(Action::Block(position, orientation))
+}
+
+// A position is written as five fields separated by dots: the walls, the two pawn cells, the
+// wall count of each player, and the player to move (1 or 2). Walls are listed in wall notation
+// without separators, in upper case when they belong to the second player, or - when there are
+// none. The starting position is "-.e1e9.10.10.1". Only letters, digits, dots and dashes are used so
+// positions can be put in URLs as they are.
+pub fn format_position(board_state: &BoardState, player_index: usize) -> String {
+    let mut walls = String::new();
+    for x in 0..8 {
+        for y in 0..8 {
+            let position = Vector2::new(x, y);
+            if let Some(orientation) = board_state.get_wall(position) {
+                let mut wall = format!("{}{}", format_cell(position), orientation_char(orientation));
+                if board_state.player_walls[x as usize][y as usize] == 1 {
+                    wall = wall.to_uppercase();
+                }
+                walls.push_str(&wall);
+            }
+        }
+    }
+    if walls.is_empty() {
+        walls.push('-');
+    }
+    format!(
+        "{}.{}{}.{}.{}.{}",
+        walls,
+        format_cell(board_state.get_player_position(0)),
+        format_cell(board_state.get_player_position(1)),
+        board_state.get_player_wall_count(0),
+        board_state.get_player_wall_count(1),
+        player_index + 1
+    )
+}
+
+pub fn parse_position(text: &str) -> Result<(BoardState, usize), String> {
+    let fields: Vec<&str> = text.split('.').collect();
+    if fields.len() != 5 || !text.is_ascii() {
+        return Err(format!("Invalid position: {}", text));
+    }
+
+    let mut board_state = BoardState::new();
+    if fields[0] != "-" {
+        if !fields[0].len().is_multiple_of(3) {
+            return Err(format!("Invalid walls: {}", fields[0]));
+        }
+        for i in (0..fields[0].len()).step_by(3) {
+            let wall = &fields[0][i..i + 3];
+            let owner = if wall.bytes().any(|c| c.is_ascii_uppercase()) {
+                1
+            } else {
+                0
+            };
+            match parse_action(&wall.to_lowercase()) {
+                Ok(Action::Block(position, orientation)) => {
+                    if is_wall_overlapping(&board_state, position, orientation) {
+                        return Err(format!("Overlapping wall: {}", wall));
+                    }
+                    board_state.set_wall(position, orientation);
+                    board_state.set_player_wall(position, owner);
+                }
+                _ => return Err(format!("Invalid wall: {}", wall)),
+            }
+        }
+    }
+
+    let pawns = fields[1];
+    let pawn_error = || format!("Invalid pawns: {}", pawns);
+    if pawns.len() != 4 {
+        return Err(pawn_error());
+    }
+    for i in 0..2 {
+        let cell = parse_cell(&pawns[i * 2..i * 2 + 2]).ok_or_else(pawn_error)?;
+        board_state.set_player_position(i, cell);
+    }
+    if board_state.get_player_position(0) == board_state.get_player_position(1) {
+        return Err(pawn_error());
+    }
+
+    for i in 0..2 {
+        let wall_count = fields[2 + i]
+            .parse::<usize>()
+            .ok()
+            .filter(|&count| count <= 10)
+            .ok_or_else(|| format!("Invalid wall count: {}", fields[2 + i]))?;
+        // Every player starts with ten walls. Positions with fewer are allowed so that puzzles
+        // don't need to place walls they don't use.
+        let placed_count = board_state
+            .player_walls
+            .iter()
+            .flatten()
+            .filter(|&&owner| owner == i as isize)
+            .count();
+        if wall_count + placed_count > 10 {
+            return Err(format!(
+                "Player {} has {} walls left but {} on the board",
+                i + 1,
+                wall_count,
+                placed_count
+            ));
+        }
+        board_state.set_player_wall_count(i, wall_count);
+    }
+
+    let player_index = match fields[4] {
+        "1" => 0,
+        "2" => 1,
+        _ => return Err(format!("Invalid player to move: {}", fields[4])),
+    };
+
+    for i in 0..2 {
+        if is_player_trapped(&board_state, i) {
+            return Err(format!("Player {} can't reach their goal", i + 1));
+        }
+    }
+    // The game ends as soon as a player reaches their goal, so the player to move can't be there.
+    if board_state.get_player_distance(player_index) == 0 {
+        return Err(format!("Player {} has already won", player_index + 1));
+    }
+    let errors = board_state.check_invariants();
+    if !errors.is_empty() {
+        return Err(format!("Inconsistent position: {}", errors.join(", ")));
+    }
+    Ok((board_state, player_index))
+}
+
+fn orientation_char(orientation: WallOrientation) -> char {
+    match orientation {
+        WallOrientation::Horizontal => 'h',
+        WallOrientation::Vertical => 'v',
+    }
+}
+
+fn parse_orientation(c: u8) -> Option<WallOrientation> {
+    match c {
+        b'h' => Some(WallOrientation::Horizontal),
+        b'v' => Some(WallOrientation::Vertical),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format_action_move() {
+        assert_eq!("e2", format_action(&Action::Move(Vector2::new(4, 1))));
+    }
+
+    #[test]
+    fn format_action_block() {
+        let action = Action::Block(Vector2::new(3, 3), WallOrientation::Horizontal);
+
+        assert_eq!("d4h", format_action(&action));
+    }
+
+    #[test]
+    fn parse_action_round_trip() {
+        for index in 0..209 {
+            let action = Action::from_index(index).unwrap();
+            assert_eq!(Ok(action), parse_action(&format_action(&action)));
+        }
+    }
+
+    #[test]
+    fn parse_action_invalid() {
+        assert_eq!(true, parse_action("j1").is_err());
+        assert_eq!(true, parse_action("a0").is_err());
+        assert_eq!(true, parse_action("i1h").is_err());
+        assert_eq!(true, parse_action("a9v").is_err());
+        assert_eq!(true, parse_action("a1x").is_err());
+        assert_eq!(true, parse_action("").is_err());
+    }
+
+    #[test]
+    fn format_position_start() {
+        assert_eq!("-.e1e9.10.10.1", format_position(&BoardState::new(), 0));
+    }
+
+    #[test]
+    fn position_round_trip() {
+        let mut board_state = BoardState::new();
+        Action::Block(Vector2::new(3, 3), WallOrientation::Horizontal).apply(&mut board_state, 0);
+        Action::Block(Vector2::new(6, 1), WallOrientation::Vertical).apply(&mut board_state, 1);
+        board_state.set_player_position(0, Vector2::new(4, 2));
+
+        let text = format_position(&board_state, 1);
+        let (parsed, player_index) = parse_position(&text).unwrap();
+
+        assert_eq!("d4hG2V.e3e9.9.9.2", text);
+        assert_eq!(1, player_index);
+        assert_eq!(text, format_position(&parsed, player_index));
+        assert_eq!(board_state.distance_matrices, parsed.distance_matrices);
+    }
+
+    #[test]
+    fn parse_position_invalid() {
+        assert_eq!(true, parse_position("").is_err());
+        assert_eq!(true, parse_position("-.e1e9.10.10").is_err());
+        assert_eq!(true, parse_position("d4.e1e9.10.10.1").is_err());
+        assert_eq!(true, parse_position("-.e1.10.10.1").is_err());
+        assert_eq!(true, parse_position("-.e1e9.x.10.1").is_err());
+        assert_eq!(true, parse_position("-.e1e9.11.10.1").is_err());
+        assert_eq!(true, parse_position("-.e1e9.10.10.3").is_err());
+    }
+
+    #[test]
+    fn parse_position_inconsistent() {
+        let error = |text: &str| parse_position(text).err().unwrap();
+
+        assert_eq!("Overlapping wall: e4h", error("d4he4h.e1e9.8.10.1"));
+        assert_eq!("Invalid pawns: e5e5", error("-.e5e5.10.10.1"));
+        assert_eq!(
+            "Invalid wall count: 18446744073709551615",
+            error("d4h.e1e9.18446744073709551615.10.1")
+        );
+        assert_eq!(
+            "Player 1 has 10 walls left but 1 on the board",
+            error("d4h.e1e9.10.10.2")
+        );
+        assert_eq!(
+            "Player 2 has 10 walls left but 1 on the board",
+            error("D4H.e1e9.10.10.1")
+        );
+        assert_eq!("Player 1 can't reach their goal", error("d1hC1VE1V.e1e9.9.8.1"));
+        assert_eq!("Player 2 has already won", error("-.e5e1.10.10.2"));
+    }
+}
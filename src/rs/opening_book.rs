@@ -0,0 +1,229 @@
+use crate::action::Action;
+use crate::board_state::BoardState;
+use crate::validation::*;
+
+use rand::Rng;
+use std::collections::HashMap;
+
+// On disk a book is the magic bytes followed by the entry count and then each entry in order of
+// its position hash. An entry is the hash, the number of moves, and the moves themselves. Each
+// move is stored as its action index and weight. All integers are little endian.
+//
+//   "QBK" version:u8 entry_count:u32 { hash:u64 move_count:u8 { action:u8 weight:u16 }* }*
+pub const MAGIC: &[u8; 3] = b"QBK";
+pub const VERSION: u8 = 1;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct BookMove {
+    pub action: Action,
+    pub weight: u16,
+}
+
+pub struct OpeningBook {
+    pub entries: HashMap<u64, Vec<BookMove>>,
+}
+
+impl OpeningBook {
+    pub fn new() -> Self {
+        OpeningBook {
+            entries: HashMap::new(),
+        }
+    }
+
+    pub fn get_moves(&self, board_state: &BoardState, player_index: usize) -> &[BookMove] {
+        match self.entries.get(&board_state.get_position_hash(player_index)) {
+            Some(moves) => moves,
+            None => &[],
+        }
+    }
+
+    // Picks one of the book moves for the position at random, weighted by how often it was
+    // played. Moves are validated since a hash collision could return moves from another position.
+    pub fn probe(&self, board_state: &BoardState, player_index: usize) -> Option<Action> {
+        self.probe_with_rng(board_state, player_index, &mut rand::thread_rng())
+    }
+
+    pub fn probe_with_rng(&self, board_state: &BoardState, player_index: usize, rng: &mut impl Rng) -> Option<Action> {
+        let moves: Vec<&BookMove> = self
+            .get_moves(board_state, player_index)
+            .iter()
+            .filter(|m| m.weight > 0 && validate_action(board_state, player_index, &m.action))
+            .collect();
+        let total_weight: u32 = moves.iter().map(|m| m.weight as u32).sum();
+        if total_weight == 0 {
+            return None;
+        }
+        let mut choice = rng.gen_range(0, total_weight);
+        for book_move in moves {
+            if choice < book_move.weight as u32 {
+                return Some(book_move.action);
+            }
+            choice -= book_move.weight as u32;
+        }
+        None
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, String> {
+        let mut reader = ByteReader { bytes, offset: 0 };
+        if reader.read(3)? != MAGIC {
+            return Err(String::from("Not an opening book"));
+        }
+        let version = reader.read(1)?[0];
+        if version != VERSION {
+            return Err(format!("Unsupported opening book version {}", version));
+        }
+        let entry_count = reader.read_u32()?;
+        let mut book = OpeningBook::new();
+        for _ in 0..entry_count {
+            let hash = reader.read_u64()?;
+            let move_count = reader.read(1)?[0];
+            let mut moves = Vec::with_capacity(move_count as usize);
+            for _ in 0..move_count {
+                let index = reader.read(1)?[0];
+                let action = Action::from_index(index).ok_or(format!("Invalid action index {}", index))?;
+                let weight = reader.read_u16()?;
+                moves.push(BookMove { action, weight });
+            }
+            book.entries.insert(hash, moves);
+        }
+        if reader.offset != bytes.len() {
+            return Err(String::from("Unexpected data after the last opening book entry"));
+        }
+        Ok(book)
+    }
+}
+
+struct ByteReader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> ByteReader<'a> {
+    fn read(&mut self, count: usize) -> Result<&'a [u8], String> {
+        if self.offset + count > self.bytes.len() {
+            return Err(String::from("Opening book is truncated"));
+        }
+        let slice = &self.bytes[self.offset..self.offset + count];
+        self.offset += count;
+        Ok(slice)
+    }
+
+    fn read_u16(&mut self) -> Result<u16, String> {
+        let mut buffer = [0; 2];
+        buffer.copy_from_slice(self.read(2)?);
+        Ok(u16::from_le_bytes(buffer))
+    }
+
+    fn read_u32(&mut self) -> Result<u32, String> {
+        let mut buffer = [0; 4];
+        buffer.copy_from_slice(self.read(4)?);
+        Ok(u32::from_le_bytes(buffer))
+    }
+
+    fn read_u64(&mut self) -> Result<u64, String> {
+        let mut buffer = [0; 8];
+        buffer.copy_from_slice(self.read(8)?);
+        Ok(u64::from_le_bytes(buffer))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vector2::Vector2;
+    use crate::wall_orientation::WallOrientation;
+
+    fn sample_game() -> Vec<Action> {
+        vec![
+            Action::Move(Vector2::new(4, 1)),
+            Action::Move(Vector2::new(4, 7)),
+            Action::Block(Vector2::new(3, 6), WallOrientation::Horizontal),
+            Action::Move(Vector2::new(3, 7)),
+        ]
+    }
+
+    #[test]
+    fn add_game_records_each_position() {
+        let mut book = OpeningBook::new();
+        book.add_game(&sample_game(), None, 10);
+
+        assert_eq!(4, book.len());
+        let moves = book.get_moves(&BoardState::new(), 0);
+        assert_eq!(
+            vec![BookMove {
+                action: Action::Move(Vector2::new(4, 1)),
+                weight: 1
+            }],
+            moves
+        );
+    }
+
+    #[test]
+    fn add_game_with_winner_only_records_winning_moves() {
+        let mut book = OpeningBook::new();
+        book.add_game(&sample_game(), Some(1), 10);
+
+        assert_eq!(2, book.len());
+        assert_eq!(true, book.get_moves(&BoardState::new(), 0).is_empty());
+    }
+
+    #[test]
+    fn add_game_respects_max_ply() {
+        let mut book = OpeningBook::new();
+        book.add_game(&sample_game(), None, 2);
+
+        assert_eq!(2, book.len());
+    }
+
+    #[test]
+    fn add_game_accumulates_weights() {
+        let mut book = OpeningBook::new();
+        book.add_game(&sample_game(), None, 1);
+        book.add_game(&sample_game(), None, 1);
+
+        assert_eq!(2, book.get_moves(&BoardState::new(), 0)[0].weight);
+    }
+
+    #[test]
+    fn probe_returns_book_move() {
+        let mut book = OpeningBook::new();
+        book.add_game(&sample_game(), None, 10);
+
+        assert_eq!(
+            Some(Action::Move(Vector2::new(4, 1))),
+            book.probe(&BoardState::new(), 0)
+        );
+        assert_eq!(None, book.probe(&BoardState::new(), 1));
+    }
+
+    #[test]
+    fn probe_skips_invalid_moves() {
+        let mut book = OpeningBook::new();
+        book.add_action(&BoardState::new(), 0, Action::Move(Vector2::new(0, 0)), 5);
+
+        assert_eq!(None, book.probe(&BoardState::new(), 0));
+    }
+
+    #[test]
+    fn bytes_round_trip() {
+        let mut book = OpeningBook::new();
+        book.add_game(&sample_game(), None, 10);
+        book.add_game(&sample_game(), Some(0), 10);
+
+        let bytes = book.to_bytes();
+        let loaded = OpeningBook::from_bytes(&bytes).unwrap();
+
+        assert_eq!(book.len(), loaded.len());
+        assert_eq!(bytes, loaded.to_bytes());
+    }
+
+    #[test]
+    fn from_bytes_rejects_truncated_data() {
+        let mut book = OpeningBook::new();
+        book.add_game(&sample_game(), None, 10);
+        let bytes = book.to_bytes();
+
+        assert_eq!(true, OpeningBook::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+        assert_eq!(true, OpeningBook::from_bytes(b"XYZ").is_err());
+    }
+}
@@ -0,0 +1,58 @@
+use crate::action::Action;
+use crate::board_state::BoardState;
+use crate::opening_book::{BookMove, OpeningBook, MAGIC, VERSION};
+
+// Building books from games and writing them in the format described in `opening_book.rs`. The
+// browser only loads books, so this is only built into the binary, and into the library's tests,
+// which build the books they load.
+impl OpeningBook {
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn add_action(&mut self, board_state: &BoardState, player_index: usize, action: Action, weight: u16) {
+        let moves = self
+            .entries
+            .entry(board_state.get_position_hash(player_index))
+            .or_default();
+        match moves.iter_mut().find(|m| m.action == action) {
+            Some(book_move) => book_move.weight = book_move.weight.saturating_add(weight),
+            None => moves.push(BookMove { action, weight }),
+        }
+    }
+
+    // Adds the first `max_ply` actions of a game played from the starting position. When the winner
+    // is known only the winner's actions are added, which lets statistics from many self-play games
+    // accumulate into weights. Without a winner every action is added, as for recorded games.
+    pub fn add_game(&mut self, actions: &[Action], winner: Option<usize>, max_ply: usize) {
+        let mut board_state = BoardState::new();
+        let mut player_index = 0;
+        for action in actions.iter().take(max_ply) {
+            if winner.is_none() || winner == Some(player_index) {
+                self.add_action(&board_state, player_index, *action, 1);
+            }
+            action.apply(&mut board_state, player_index);
+            player_index = 1 - player_index;
+        }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut hashes: Vec<&u64> = self.entries.keys().collect();
+        hashes.sort();
+
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(MAGIC);
+        bytes.push(VERSION);
+        bytes.extend_from_slice(&(hashes.len() as u32).to_le_bytes());
+        for hash in hashes {
+            let moves = &self.entries[hash];
+            bytes.extend_from_slice(&hash.to_le_bytes());
+            bytes.push(moves.len() as u8);
+            for book_move in moves {
+                bytes.push(book_move.action.to_index());
+                bytes.extend_from_slice(&book_move.weight.to_le_bytes());
+            }
+        }
+        bytes
+    }
+}
@@ -0,0 +1,362 @@
+use crate::action::Action;
+use crate::board_state::{BoardState, WallUndoStack};
+use crate::iterative_search::{SearchInfo, SearchLimits};
+use crate::minimax_player::*;
+use crate::player::{check_can_act, Player};
+use crate::validation::*;
+
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Instant;
+
+// Splits the search at the root: each thread repeatedly takes the next root action that nobody
+// has searched yet and searches it on its own copy of the board. The best score so far is shared,
+// so later actions are searched with the same windows the single-threaded search would give them.
+// Ties go to the action generated first whichever thread finishes first, so the score, the chosen
+// action and the principal variation are all the same as `MinimaxPlayer`'s. Only the number of
+// nodes searched depends on how the threads happen to interleave.
+pub struct ParallelMinimaxPlayer {
+    pub branch_depth: usize,
+    pub thread_count: usize,
+    last_report: Option<SearchReport>,
+}
+
+// The best root action found so far, shared between the threads.
+struct RootBest {
+    score: i32,
+    // The generated index of the best action, or `usize::MAX` before any action is scored.
+    index: usize,
+    principal_variation: Vec<Action>,
+}
+
+struct RootResult {
+    score: i32,
+    principal_variation: Vec<Action>,
+    nodes: usize,
+    cutoffs: usize,
+    aborted: bool,
+}
+
+impl ParallelMinimaxPlayer {
+    pub fn new(branch_depth: usize, thread_count: usize) -> Self {
+        ParallelMinimaxPlayer {
+            branch_depth: branch_depth.max(1),
+            thread_count: thread_count.max(1),
+            last_report: None,
+        }
+    }
+
+    // The same as `MinimaxPlayer::take_action_with_report`, searched on several threads.
+    pub fn take_action_with_report(
+        board_state: &BoardState,
+        player_index: usize,
+        branch_depth: usize,
+        thread_count: usize,
+    ) -> Result<SearchReport, String> {
+        check_can_act(board_state, player_index)?;
+        let start = Instant::now();
+        if let Some(report) = MinimaxPlayer::solve_with_report(board_state, player_index) {
+            return Ok(report);
+        }
+        let branch_depth = branch_depth.max(1);
+        let window = (-INFINITE_SCORE, INFINITE_SCORE);
+        let result = search_root(
+            board_state,
+            player_index,
+            branch_depth,
+            window,
+            None,
+            None,
+            thread_count,
+        );
+        let best_action = result
+            .principal_variation
+            .first()
+            .copied()
+            .ok_or_else(|| format!("Player {} has no legal actions", player_index + 1))?;
+        Ok(SearchReport {
+            best_action,
+            score: result.score,
+            result: get_forced_result(result.score),
+            principal_variation: result.principal_variation,
+            depth: branch_depth,
+            nodes: result.nodes,
+            cutoffs: result.cutoffs,
+            elapsed_ms: start.elapsed().as_secs_f64() * 1000.0,
+            is_solved: false,
+        })
+    }
+
+    // The same as `MinimaxPlayer::search`, with each iteration searched on several threads.
+    pub fn search(
+        board_state: &BoardState,
+        player_index: usize,
+        limits: &SearchLimits,
+        thread_count: usize,
+        on_iteration: &mut dyn FnMut(&SearchInfo),
+    ) -> SearchInfo {
+        let deadline = limits.time_limit.map(|time_limit| Instant::now() + time_limit);
+        let mut info = SearchInfo {
+            depth: 0,
+            score: 0,
+            nodes: 0,
+            principal_variation: Vec::new(),
+        };
+        for depth in 1..=limits.max_depth.max(1) {
+            // As in the single-threaded search, the first iteration ignores the limits.
+            let (deadline, stop) = if depth == 1 {
+                (None, None)
+            } else {
+                (deadline, limits.stop.clone())
+            };
+            let mut window = get_aspiration_window(depth, info.score);
+            let result = loop {
+                let result = search_root(
+                    board_state,
+                    player_index,
+                    depth,
+                    window,
+                    deadline,
+                    stop.clone(),
+                    thread_count,
+                );
+                info.nodes += result.nodes;
+                if result.score <= window.0 && !result.aborted {
+                    window.0 = -INFINITE_SCORE;
+                } else if result.score >= window.1 && !result.aborted {
+                    window.1 = INFINITE_SCORE;
+                } else {
+                    break result;
+                }
+            };
+            if result.aborted {
+                break;
+            }
+            info = SearchInfo {
+                depth,
+                score: result.score,
+                nodes: info.nodes,
+                principal_variation: result.principal_variation,
+            };
+            on_iteration(&info);
+        }
+        info
+    }
+}
+
+impl Player for ParallelMinimaxPlayer {
+    fn get_name(&self) -> String {
+        format!("Minimax {} ({} threads)", self.branch_depth, self.thread_count)
+    }
+
+    fn choose_action(&mut self, board_state: &BoardState, player_index: usize) -> Result<Action, String> {
+        let report = ParallelMinimaxPlayer::take_action_with_report(
+            board_state,
+            player_index,
+            self.branch_depth,
+            self.thread_count,
+        )?;
+        let action = report.best_action;
+        self.last_report = Some(report);
+        Ok(action)
+    }
+
+    fn get_last_report(&self) -> Option<&SearchReport> {
+        self.last_report.as_ref()
+    }
+}
+
+// Searches the root with the given window, which is what `MinimaxBoardNode::build_children` does
+// for the root of a single-threaded search. The deadline and stop flag apply to every thread.
+fn search_root(
+    board_state: &BoardState,
+    player_index: usize,
+    depth: usize,
+    window: (i32, i32),
+    deadline: Option<Instant>,
+    stop: Option<Arc<AtomicBool>>,
+    thread_count: usize,
+) -> RootResult {
+    let (original_alpha, beta) = window;
+    let mut actions = get_valid_move_actions(board_state, player_index);
+    actions.append(&mut get_valid_block_actions(board_state, player_index));
+    let mut root_board_state = *board_state;
+    let actions = order_actions(
+        &mut root_board_state,
+        player_index,
+        0,
+        actions,
+        Evaluation::Distance,
+        &mut WallUndoStack::default(),
+    );
+
+    let next_action = AtomicUsize::new(0);
+    let is_cut_off = AtomicBool::new(false);
+    let best = Mutex::new(RootBest {
+        score: -INFINITE_SCORE,
+        index: usize::MAX,
+        principal_variation: Vec::new(),
+    });
+    let worker_results: Vec<(usize, usize, bool)> = thread::scope(|scope| {
+        let workers: Vec<_> = (0..thread_count.max(1))
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut board_state = *board_state;
+                    let mut context = SearchContext::new(deadline, stop.clone());
+                    loop {
+                        let position = next_action.fetch_add(1, Ordering::Relaxed);
+                        if position >= actions.len() || is_cut_off.load(Ordering::Relaxed) || context.aborted {
+                            break;
+                        }
+                        let (index, action) = actions[position];
+                        let (best_score, best_index) = {
+                            let best = best.lock().unwrap();
+                            (best.score, best.index)
+                        };
+                        let alpha = original_alpha.max(best_score);
+                        let undo_record = action.make(&mut board_state, player_index, &mut context.undo_stack);
+                        let mut search_child = |alpha: i32, beta: i32, context: &mut SearchContext| {
+                            let mut child_node = MinimaxBoardNode::new(&mut board_state, 1 - player_index, 1);
+                            child_node.build_children(depth - 1, -beta, -alpha, context);
+                            (-child_node.score, child_node.principal_variation)
+                        };
+                        let (score, mut principal_variation) = if best_index == usize::MAX {
+                            search_child(alpha, beta, &mut context)
+                        } else {
+                            let threshold =
+                                get_null_window_threshold(index, best_index, best_score, original_alpha, alpha);
+                            let (score, principal_variation) = search_child(threshold, threshold + 1, &mut context);
+                            if depth > 2 && score > threshold && score < beta {
+                                search_child(threshold, beta, &mut context)
+                            } else {
+                                (score, principal_variation)
+                            }
+                        };
+                        undo_record.unmake(&mut board_state, &mut context.undo_stack);
+                        if context.aborted {
+                            break;
+                        }
+
+                        let mut best = best.lock().unwrap();
+                        if is_better_child(score, index, best.score, best.index) {
+                            best.score = score;
+                            best.index = index;
+                            best.principal_variation = vec![action];
+                            best.principal_variation.append(&mut principal_variation);
+                        }
+                        if best.score >= beta {
+                            is_cut_off.store(true, Ordering::Relaxed);
+                        }
+                    }
+                    (context.nodes, context.cutoffs, context.aborted)
+                })
+            })
+            .collect();
+        workers.into_iter().map(|worker| worker.join().unwrap()).collect()
+    });
+
+    let best = best.into_inner().unwrap();
+    let is_cut_off = is_cut_off.into_inner();
+    RootResult {
+        score: best.score,
+        principal_variation: best.principal_variation,
+        nodes: 1 + worker_results.iter().map(|&(nodes, _, _)| nodes).sum::<usize>(),
+        cutoffs: worker_results.iter().map(|&(_, cutoffs, _)| cutoffs).sum::<usize>() + is_cut_off as usize,
+        aborted: worker_results.iter().any(|&(_, _, aborted)| aborted),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notation::parse_position;
+
+    const POSITIONS: [&str; 4] = [
+        "-.e1e9.10.10.1",
+        "d4hE6V.e3e7.9.9.1",
+        "c7hf7hD2VG3V.d5e6.8.8.2",
+        "d7hf7hD5VF5VH8H.e6e4.6.7.1",
+    ];
+
+    #[test]
+    fn matches_single_threaded_search() {
+        for position in POSITIONS.iter() {
+            let (board_state, player_index) = parse_position(position).unwrap();
+            let expected = MinimaxPlayer::take_action_with_report(&board_state, player_index, 2).unwrap();
+            for &thread_count in [1, 2, 4].iter() {
+                let report =
+                    ParallelMinimaxPlayer::take_action_with_report(&board_state, player_index, 2, thread_count)
+                        .unwrap();
+
+                assert_eq!(expected.score, report.score, "{} {}", position, thread_count);
+                if thread_count == 1 {
+                    assert_eq!(expected.nodes, report.nodes, "{}", position);
+                }
+                assert_eq!(
+                    expected.principal_variation, report.principal_variation,
+                    "{} {}",
+                    position, thread_count
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn search_matches_single_threaded_search() {
+        let (board_state, player_index) = parse_position(POSITIONS[1]).unwrap();
+        let limits = SearchLimits {
+            max_depth: 3,
+            time_limit: None,
+            stop: None,
+        };
+        let expected = MinimaxPlayer::search(&board_state, player_index, &limits, &mut |_| {});
+
+        let mut depths = Vec::new();
+        let info = ParallelMinimaxPlayer::search(&board_state, player_index, &limits, 3, &mut |info| {
+            depths.push(info.depth)
+        });
+
+        assert_eq!(vec![1, 2, 3], depths);
+        assert_eq!(expected.score, info.score);
+        assert_eq!(expected.principal_variation, info.principal_variation);
+    }
+
+    #[test]
+    fn search_stops_when_asked() {
+        let stop = Arc::new(AtomicBool::new(true));
+        let limits = SearchLimits {
+            max_depth: 5,
+            time_limit: None,
+            stop: Some(stop),
+        };
+
+        let info = ParallelMinimaxPlayer::search(&BoardState::new(), 0, &limits, 2, &mut |_| {});
+
+        // The first iteration always completes so that there is an action to play.
+        assert_eq!(1, info.depth);
+        assert_eq!(false, info.principal_variation.is_empty());
+    }
+
+    #[test]
+    fn uses_endgame_solver() {
+        let (board_state, player_index) = parse_position("-.e8a5.0.0.1").unwrap();
+
+        let report = ParallelMinimaxPlayer::take_action_with_report(&board_state, player_index, 2, 2).unwrap();
+
+        assert_eq!(true, report.is_solved);
+        assert_eq!(
+            MinimaxPlayer::take_action(&board_state, player_index, 2).unwrap(),
+            report.best_action
+        );
+    }
+
+    #[test]
+    fn search_fails_when_game_is_over() {
+        let (board_state, _) = parse_position("-.e9e5.10.10.2").unwrap();
+
+        let report = ParallelMinimaxPlayer::take_action_with_report(&board_state, 1, 2, 2);
+
+        assert_eq!(Some(String::from("The game is over")), report.err());
+    }
+}
@@ -0,0 +1,106 @@
+use crate::action::Action;
+use crate::board_state::BoardState;
+use crate::validation::*;
+
+// Counts the sequences of legal actions of the given length from a position. The counts for the
+// starting position are fixed by the rules, so any change to them means legality has changed.
+// Once a player reaches their goal the game is over and there are no further actions.
+pub fn perft(board_state: &BoardState, player_index: usize, depth: usize) -> u64 {
+    if depth == 0 {
+        return 1;
+    }
+    let actions = get_legal_actions(board_state, player_index);
+    if depth == 1 {
+        return actions.len() as u64;
+    }
+    actions
+        .iter()
+        .map(|action| {
+            perft(
+                &board_state.from_action(action, player_index),
+                1 - player_index,
+                depth - 1,
+            )
+        })
+        .sum()
+}
+
+// The perft count below each legal first action, which narrows down where two move generators
+// disagree.
+pub fn perft_divide(board_state: &BoardState, player_index: usize, depth: usize) -> Vec<(Action, u64)> {
+    if depth == 0 {
+        return Vec::new();
+    }
+    get_legal_actions(board_state, player_index)
+        .iter()
+        .map(|&action| {
+            let new_board_state = board_state.from_action(&action, player_index);
+            (action, perft(&new_board_state, 1 - player_index, depth - 1))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notation::parse_position;
+    use crate::vector2::Vector2;
+    use crate::wall_orientation::WallOrientation;
+
+    #[test]
+    fn perft_start_position() {
+        let board_state = BoardState::new();
+
+        assert_eq!(1, perft(&board_state, 0, 0));
+        assert_eq!(131, perft(&board_state, 0, 1));
+        assert_eq!(16677, perft(&board_state, 0, 2));
+    }
+
+    // Too slow for debug builds, run with `cargo test --release -- --ignored`.
+    #[test]
+    #[ignore]
+    fn perft_start_position_depth_3() {
+        assert_eq!(2062264, perft(&BoardState::new(), 0, 3));
+    }
+
+    #[test]
+    fn perft_excludes_trapping_walls() {
+        // The walls at c1v and e1v enclose d1 and e1 on both sides, so closing the top with d1h
+        // would trap the first player.
+        let (board_state, player_index) = parse_position("c1vE1V.d1e9.9.9.1").unwrap();
+
+        let actions = get_legal_actions(&board_state, player_index);
+
+        assert_eq!(
+            false,
+            actions.contains(&Action::Block(Vector2::new(3, 0), WallOrientation::Horizontal))
+        );
+        assert_eq!(actions.len() as u64, perft(&board_state, player_index, 1));
+    }
+
+    #[test]
+    fn perft_stops_when_game_is_over() {
+        let (board_state, player_index) = parse_position("-.e9e5.10.10.2").unwrap();
+
+        assert_eq!(0, perft(&board_state, player_index, 1));
+    }
+
+    #[test]
+    fn perft_divide_sums_to_perft() {
+        let board_state = BoardState::new();
+
+        let divide = perft_divide(&board_state, 0, 2);
+
+        assert_eq!(131, divide.len());
+        assert_eq!(
+            perft(&board_state, 0, 2),
+            divide.iter().map(|(_, count)| count).sum::<u64>()
+        );
+        assert_eq!(
+            Some(&(Action::Move(Vector2::new(4, 1)), 131)),
+            divide
+                .iter()
+                .find(|(action, _)| *action == Action::Move(Vector2::new(4, 1)))
+        );
+    }
+}
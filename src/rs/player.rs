@@ -0,0 +1,34 @@
+use crate::action::Action;
+use crate::board_state::BoardState;
+use crate::minimax_player::SearchReport;
+use crate::validation::has_legal_action;
+
+// Something that chooses the actions for one side of a game, such as one of the bots or an
+// external engine. Failures are returned rather than panicking so that a misbehaving player
+// forfeits its game instead of ending the whole run.
+pub trait Player {
+    fn get_name(&self) -> String;
+
+    fn choose_action(&mut self, board_state: &BoardState, player_index: usize) -> Result<Action, String>;
+
+    // What the player found while choosing its last action, for players that search.
+    fn get_last_report(&self) -> Option<&SearchReport> {
+        None
+    }
+
+    // Players that choose at random draw from a generator seeded with this, so that a game can be
+    // played again with the same choices.
+    fn set_seed(&mut self, _seed: u64) {}
+}
+
+// The bots assume that the game is still going and that they have something to do, which isn't
+// guaranteed for positions that come from outside, so they check this before choosing.
+pub fn check_can_act(board_state: &BoardState, player_index: usize) -> Result<(), String> {
+    if board_state.get_player_distance(0) == 0 || board_state.get_player_distance(1) == 0 {
+        return Err(String::from("The game is over"));
+    }
+    if !has_legal_action(board_state, player_index) {
+        return Err(format!("Player {} has no legal actions", player_index + 1));
+    }
+    Ok(())
+}
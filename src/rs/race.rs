@@ -0,0 +1,79 @@
+use crate::board_state::BoardState;
+use crate::validation::get_accessible_cells;
+use crate::vector2::Vector2;
+
+// Pawns that keep getting in each other's way could shuffle forever, so races are cut short after
+// this many plies.
+const MAX_RACE_PLIES: usize = 2 * 81;
+
+// Each player's distance to their goal row, counted in their own moves, when both pawns race there
+// taking turns with `player_index` moving first. Unlike the distance matrices this accounts for
+// the pawns getting in each other's way, so a pawn can gain a move jumping the other one or lose
+// one stepping around it. Each pawn takes the move that leaves it closest to its goal, the first
+// one generated on a tie, so neither player uses their pawn to block. Once one pawn arrives the
+// other finishes alone.
+pub fn get_race_distances(board_state: &BoardState, player_index: usize) -> [isize; 2] {
+    let get_distance = |player_index: usize, position: Vector2<isize>| {
+        board_state.distance_matrices[player_index][position.x as usize][position.y as usize]
+    };
+    let mut positions = board_state.player_positions;
+    let mut move_counts = [0; 2];
+    let mut player_index = player_index;
+    for _ in 0..MAX_RACE_PLIES {
+        if get_distance(0, positions[0]) == 0 || get_distance(1, positions[1]) == 0 {
+            break;
+        }
+        // A pawn boxed in by walls and the other pawn has to wait for it to move away.
+        if let Some(position) = get_accessible_cells(board_state, positions[player_index], positions[1 - player_index])
+            .into_iter()
+            .min_by_key(|&position| get_distance(player_index, position))
+        {
+            positions[player_index] = position;
+        }
+        move_counts[player_index] += 1;
+        player_index = 1 - player_index;
+    }
+    [
+        move_counts[0] + get_distance(0, positions[0]),
+        move_counts[1] + get_distance(1, positions[1]),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notation::parse_position;
+
+    #[test]
+    fn matches_distances_when_pawns_stay_apart() {
+        let (board_state, player_index) = parse_position("-.a1i9.10.10.1").unwrap();
+
+        assert_eq!([8, 8], get_race_distances(&board_state, player_index));
+    }
+
+    #[test]
+    fn pawns_meet_from_start() {
+        // Both pawns head straight up the e file, so the first to reach the middle is jumped.
+        assert_eq!([8, 7], get_race_distances(&BoardState::new(), 0));
+        assert_eq!([7, 8], get_race_distances(&BoardState::new(), 1));
+    }
+
+    #[test]
+    fn counts_jump() {
+        // Whoever steps up to the other pawn lets it jump, which saves it a move.
+        let (board_state, _) = parse_position("-.e4e6.10.10.1").unwrap();
+
+        assert_eq!([5, 4], get_race_distances(&board_state, 0));
+        assert_eq!([4, 5], get_race_distances(&board_state, 1));
+    }
+
+    #[test]
+    fn counts_diagonal_jump_past_wall() {
+        // The wall behind player 2's pawn makes player 1 walk around it, unless player 1 jumps
+        // diagonally over the pawn first.
+        let (board_state, _) = parse_position("d5h.e4e5.9.10.1").unwrap();
+
+        assert_eq!(6, board_state.get_player_distance(0));
+        assert_eq!([5, 4], get_race_distances(&board_state, 0));
+    }
+}
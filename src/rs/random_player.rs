@@ -1,21 +1,45 @@
 use crate::action::Action;
 use crate::board_state::BoardState;
+use crate::endgame_solver::EndgameSolver;
+use crate::player::{check_can_act, Player};
 use crate::validation::*;
 use crate::vector2::Vector2;
 use crate::wall_orientation::WallOrientation;
 
+use rand::rngs::StdRng;
 use rand::seq::SliceRandom;
-use rand::Rng;
+use rand::{Rng, SeedableRng};
 
-pub struct RandomPlayer {}
+pub struct RandomPlayer {
+    pub move_chance: f32,
+    rng: StdRng,
+}
 
 impl RandomPlayer {
+    pub fn new(move_chance: f32) -> Self {
+        RandomPlayer {
+            move_chance,
+            rng: StdRng::from_entropy(),
+        }
+    }
+
     pub fn take_action(board_state: &BoardState, player_index: usize, move_chance: f32) -> Action {
-        let mut rng = rand::thread_rng();
+        RandomPlayer::take_action_with_rng(board_state, player_index, move_chance, &mut rand::thread_rng())
+    }
+
+    pub fn take_action_with_rng(
+        board_state: &BoardState,
+        player_index: usize,
+        move_chance: f32,
+        rng: &mut impl Rng,
+    ) -> Action {
+        if let Some(action) = EndgameSolver::take_action(board_state, player_index) {
+            return action;
+        }
         loop {
             if rng.gen::<f32>() < move_chance || board_state.get_player_wall_count(player_index) == 0 {
                 let valid_moves = get_valid_move_positions(board_state, player_index);
-                let rand_move = valid_moves.choose(&mut rng).unwrap();
+                let rand_move = valid_moves.choose(rng).unwrap();
                 let action = Action::Move(*rand_move);
                 return action;
             } else {
@@ -33,3 +57,23 @@ impl RandomPlayer {
         }
     }
 }
+
+impl Player for RandomPlayer {
+    fn get_name(&self) -> String {
+        String::from("Random")
+    }
+
+    fn choose_action(&mut self, board_state: &BoardState, player_index: usize) -> Result<Action, String> {
+        check_can_act(board_state, player_index)?;
+        Ok(RandomPlayer::take_action_with_rng(
+            board_state,
+            player_index,
+            self.move_chance,
+            &mut self.rng,
+        ))
+    }
+
+    fn set_seed(&mut self, seed: u64) {
+        self.rng = StdRng::seed_from_u64(seed);
+    }
+}
@@ -0,0 +1,118 @@
+use crate::action::Action;
+use crate::board_state::{BoardState, WallUndoStack};
+use crate::minimax_player::{evaluate_with, Evaluation, INFINITE_SCORE};
+use crate::validation::*;
+
+// Two plain alpha-beta searches that differ only in how they reach each child, so that timing them
+// on the same position shows what making and unmaking actions on one board saves over copying the
+// board for every node, as the search used to. Neither orders its actions, so both visit the same
+// nodes. Each returns the score from the side of the player to move and the nodes it visited.
+
+pub fn search_with_copies(
+    board_state: &BoardState,
+    player_index: usize,
+    depth: usize,
+    ply: usize,
+    mut alpha: i32,
+    beta: i32,
+) -> (i32, usize) {
+    if is_leaf(board_state, depth) {
+        return (evaluate_with(Evaluation::Distance, board_state, player_index, ply), 1);
+    }
+    let mut nodes = 1;
+    let mut score = -INFINITE_SCORE;
+    for action in get_actions(board_state, player_index) {
+        let child = board_state.from_action(&action, player_index);
+        if is_either_player_trapped(&child) {
+            continue;
+        }
+        let (child_score, child_nodes) =
+            search_with_copies(&child, 1 - player_index, depth - 1, ply + 1, -beta, -alpha);
+        nodes += child_nodes;
+        score = score.max(-child_score);
+        alpha = alpha.max(score);
+        if alpha >= beta {
+            break;
+        }
+    }
+    (score, nodes)
+}
+
+pub fn search_with_undo(
+    board_state: &mut BoardState,
+    player_index: usize,
+    depth: usize,
+    ply: usize,
+    mut alpha: i32,
+    beta: i32,
+    undo_stack: &mut WallUndoStack,
+) -> (i32, usize) {
+    if is_leaf(board_state, depth) {
+        return (evaluate_with(Evaluation::Distance, board_state, player_index, ply), 1);
+    }
+    let mut nodes = 1;
+    let mut score = -INFINITE_SCORE;
+    for action in get_actions(board_state, player_index) {
+        let undo_record = action.make(board_state, player_index, undo_stack);
+        if is_either_player_trapped(board_state) {
+            undo_record.unmake(board_state, undo_stack);
+            continue;
+        }
+        let (child_score, child_nodes) = search_with_undo(
+            board_state,
+            1 - player_index,
+            depth - 1,
+            ply + 1,
+            -beta,
+            -alpha,
+            undo_stack,
+        );
+        undo_record.unmake(board_state, undo_stack);
+        nodes += child_nodes;
+        score = score.max(-child_score);
+        alpha = alpha.max(score);
+        if alpha >= beta {
+            break;
+        }
+    }
+    (score, nodes)
+}
+
+fn is_leaf(board_state: &BoardState, depth: usize) -> bool {
+    depth == 0 || board_state.get_player_distance(0) == 0 || board_state.get_player_distance(1) == 0
+}
+
+fn get_actions(board_state: &BoardState, player_index: usize) -> Vec<Action> {
+    let mut actions = get_valid_move_actions(board_state, player_index);
+    actions.append(&mut get_valid_block_actions(board_state, player_index));
+    actions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notation::parse_position;
+
+    #[test]
+    fn searches_visit_same_nodes() {
+        for &position in ["-.e1e9.10.10.1", "d4hE6V.e3e7.9.9.1", "c7hf7hD2VG3V.d5e6.8.8.2"].iter() {
+            let (board_state, player_index) = parse_position(position).unwrap();
+
+            let expected = search_with_copies(&board_state, player_index, 2, 0, -INFINITE_SCORE, INFINITE_SCORE);
+            let mut undo_board_state = board_state;
+            let mut undo_stack = WallUndoStack::default();
+            let result = search_with_undo(
+                &mut undo_board_state,
+                player_index,
+                2,
+                0,
+                -INFINITE_SCORE,
+                INFINITE_SCORE,
+                &mut undo_stack,
+            );
+
+            assert_eq!(expected, result, "{}", position);
+            assert_eq!(true, board_state == undo_board_state, "{}", position);
+        }
+    }
+}
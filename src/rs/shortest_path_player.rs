@@ -1,16 +1,40 @@
 use crate::action::Action;
 use crate::board_state::BoardState;
+use crate::endgame_solver::EndgameSolver;
+use crate::player::{check_can_act, Player};
 use crate::validation::*;
 use crate::vector2::Vector2;
 use crate::wall_orientation::WallOrientation;
 
-use rand::Rng;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
 
-pub struct ShortestPathPlayer {}
+pub struct ShortestPathPlayer {
+    pub move_chance: f32,
+    rng: StdRng,
+}
 
 impl ShortestPathPlayer {
+    pub fn new(move_chance: f32) -> Self {
+        ShortestPathPlayer {
+            move_chance,
+            rng: StdRng::from_entropy(),
+        }
+    }
+
     pub fn take_action(board_state: &BoardState, player_index: usize, move_chance: f32) -> Action {
-        let mut rng = rand::thread_rng();
+        ShortestPathPlayer::take_action_with_rng(board_state, player_index, move_chance, &mut rand::thread_rng())
+    }
+
+    pub fn take_action_with_rng(
+        board_state: &BoardState,
+        player_index: usize,
+        move_chance: f32,
+        rng: &mut impl Rng,
+    ) -> Action {
+        if let Some(action) = EndgameSolver::take_action(board_state, player_index) {
+            return action;
+        }
         loop {
             if rng.gen::<f32>() < move_chance || board_state.get_player_wall_count(player_index) == 0 {
                 // Move along shortest path
@@ -47,6 +71,26 @@ impl ShortestPathPlayer {
     }
 }
 
+impl Player for ShortestPathPlayer {
+    fn get_name(&self) -> String {
+        String::from("Shortest Path")
+    }
+
+    fn choose_action(&mut self, board_state: &BoardState, player_index: usize) -> Result<Action, String> {
+        check_can_act(board_state, player_index)?;
+        Ok(ShortestPathPlayer::take_action_with_rng(
+            board_state,
+            player_index,
+            self.move_chance,
+            &mut self.rng,
+        ))
+    }
+
+    fn set_seed(&mut self, seed: u64) {
+        self.rng = StdRng::seed_from_u64(seed);
+    }
+}
+
 fn get_best_move(board_state: &BoardState, player_index: usize, distance_matrix: &[[isize; 9]; 9]) -> Vector2<isize> {
     let mut best_distance = -1;
     let mut best_move = Vector2::new(-1, -1);
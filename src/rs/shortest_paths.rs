@@ -0,0 +1,188 @@
+use crate::board_state::{BoardState, DIRECTIONS};
+use crate::vector2::Vector2;
+
+use serde::Serialize;
+use tsify::Tsify;
+
+// The routes from a player's pawn to their goal row, for highlighting on the board. Like the
+// distance matrices these only follow the walls, so they ignore the other pawn and jumps. The path
+// and the cells include the pawn's cell and end on the goal row. The count saturates rather than
+// overflowing, although no board comes close.
+#[derive(Clone, Debug, PartialEq, Serialize, Tsify)]
+pub struct ShortestPaths {
+    pub path: Vec<Vector2<isize>>,
+    pub cells: Vec<Vector2<isize>>,
+    pub path_count: u64,
+}
+
+pub fn get_shortest_paths(board_state: &BoardState, player_index: usize) -> ShortestPaths {
+    let on_path = get_shortest_path_cells(board_state, player_index);
+    let mut cells = Vec::new();
+    for (x, column) in on_path.iter().enumerate() {
+        for (y, &is_on_path) in column.iter().enumerate() {
+            if is_on_path {
+                cells.push(Vector2::new(x as isize, y as isize));
+            }
+        }
+    }
+    ShortestPaths {
+        path: get_shortest_path(board_state, player_index),
+        cells,
+        path_count: count_shortest_paths(board_state, player_index),
+    }
+}
+
+// Follows the distances down from the pawn, taking the first step in `DIRECTIONS` order when there
+// is a choice.
+pub fn get_shortest_path(board_state: &BoardState, player_index: usize) -> Vec<Vector2<isize>> {
+    let mut cell = board_state.get_player_position(player_index);
+    let mut path = vec![cell];
+    while get_distance(board_state, player_index, cell) > 0 {
+        cell = get_next_cells(board_state, player_index, cell).next().unwrap();
+        path.push(cell);
+    }
+    path
+}
+
+// Every cell that lies on at least one shortest path, indexed by x and then y.
+pub fn get_shortest_path_cells(board_state: &BoardState, player_index: usize) -> [[bool; 9]; 9] {
+    let mut on_path = [[false; 9]; 9];
+    let start = board_state.get_player_position(player_index);
+    on_path[start.x as usize][start.y as usize] = true;
+    let mut stack = vec![start];
+    while let Some(cell) = stack.pop() {
+        for next_cell in get_next_cells(board_state, player_index, cell) {
+            if !on_path[next_cell.x as usize][next_cell.y as usize] {
+                on_path[next_cell.x as usize][next_cell.y as usize] = true;
+                stack.push(next_cell);
+            }
+        }
+    }
+    on_path
+}
+
+pub fn count_shortest_paths(board_state: &BoardState, player_index: usize) -> u64 {
+    // The number of shortest paths from each cell, filled in by increasing distance so that the
+    // counts of the next cells are known before they are needed.
+    let distance_matrix = board_state.get_distance_matrix(player_index);
+    let mut cells = Vec::new();
+    for (x, column) in distance_matrix.iter().enumerate() {
+        for (y, &distance) in column.iter().enumerate() {
+            if distance >= 0 {
+                cells.push(Vector2::new(x as isize, y as isize));
+            }
+        }
+    }
+    cells.sort_by_key(|cell| distance_matrix[cell.x as usize][cell.y as usize]);
+    let mut counts = [[0u64; 9]; 9];
+    for cell in cells {
+        counts[cell.x as usize][cell.y as usize] = if get_distance(board_state, player_index, cell) == 0 {
+            1
+        } else {
+            get_next_cells(board_state, player_index, cell).fold(0u64, |count, next_cell| {
+                count.saturating_add(counts[next_cell.x as usize][next_cell.y as usize])
+            })
+        };
+    }
+    let start = board_state.get_player_position(player_index);
+    counts[start.x as usize][start.y as usize]
+}
+
+fn get_distance(board_state: &BoardState, player_index: usize, cell: Vector2<isize>) -> isize {
+    board_state.distance_matrices[player_index][cell.x as usize][cell.y as usize]
+}
+
+// The neighbouring cells one step closer to the goal row.
+fn get_next_cells(
+    board_state: &BoardState,
+    player_index: usize,
+    cell: Vector2<isize>,
+) -> impl Iterator<Item = Vector2<isize>> + '_ {
+    let distance = get_distance(board_state, player_index, cell);
+    (0..4)
+        .filter(move |&i| board_state.cell_connections[cell.x as usize][cell.y as usize][i])
+        .map(move |i| cell + DIRECTIONS[i])
+        .filter(move |&next_cell| get_distance(board_state, player_index, next_cell) == distance - 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notation::parse_position;
+    use crate::validation::get_valid_move_positions;
+
+    #[test]
+    fn straight_path_on_open_board() {
+        let board_state = BoardState::new();
+
+        let paths = get_shortest_paths(&board_state, 0);
+
+        let expected: Vec<_> = (0..9).map(|y| Vector2::new(4, y)).collect();
+        assert_eq!(expected, paths.path);
+        assert_eq!(expected, paths.cells);
+        assert_eq!(1, paths.path_count);
+    }
+
+    #[test]
+    fn counts_paths_around_wall() {
+        // The wall covers the e and d files on the way up from e2, so the pawn has to step over to
+        // the f file, either before moving up or after.
+        let (board_state, _) = parse_position("d2h.e1e9.9.10.2").unwrap();
+
+        let paths = get_shortest_paths(&board_state, 0);
+
+        let mut expected_path = vec![Vector2::new(4, 0), Vector2::new(5, 0)];
+        expected_path.extend((1..9).map(|y| Vector2::new(5, y)));
+        assert_eq!(expected_path, paths.path);
+        let mut expected_cells = vec![Vector2::new(4, 0), Vector2::new(4, 1)];
+        expected_cells.extend((0..9).map(|y| Vector2::new(5, y)));
+        assert_eq!(expected_cells, paths.cells);
+        assert_eq!(2, paths.path_count);
+    }
+
+    #[test]
+    fn paths_follow_the_distances() {
+        let (board_state, _) = parse_position("c7hf7hD2VG3V.d5e6.8.8.2").unwrap();
+        for player_index in 0..2 {
+            let path = get_shortest_path(&board_state, player_index);
+            let cells = get_shortest_path_cells(&board_state, player_index);
+
+            assert_eq!(board_state.get_player_distance(player_index) as usize + 1, path.len());
+            for (i, cell) in path.iter().enumerate() {
+                assert_eq!(
+                    path.len() - 1 - i,
+                    board_state.distance_matrices[player_index][cell.x as usize][cell.y as usize] as usize
+                );
+                assert_eq!(true, cells[cell.x as usize][cell.y as usize]);
+            }
+            assert_eq!(true, count_shortest_paths(&board_state, player_index) >= 1);
+        }
+    }
+
+    #[test]
+    fn count_matches_enumeration() {
+        fn count(board_state: &BoardState, player_index: usize, cell: Vector2<isize>) -> u64 {
+            if get_distance(board_state, player_index, cell) == 0 {
+                return 1;
+            }
+            get_next_cells(board_state, player_index, cell)
+                .map(|next_cell| count(board_state, player_index, next_cell))
+                .sum()
+        }
+        let (board_state, player_index) = parse_position("d4hE6V.e3e7.9.9.1").unwrap();
+        // The pawns' own cells are checked, along with every cell they could move to.
+        for player_index in [player_index, 1 - player_index].iter().copied() {
+            let mut board_state = board_state;
+            let mut positions = get_valid_move_positions(&board_state, player_index);
+            positions.push(board_state.get_player_position(player_index));
+            for position in positions {
+                board_state.set_player_position(player_index, position);
+
+                assert_eq!(
+                    count(&board_state, player_index, position),
+                    count_shortest_paths(&board_state, player_index)
+                );
+            }
+        }
+    }
+}
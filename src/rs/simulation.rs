@@ -0,0 +1,297 @@
+use crate::board_state::BoardState;
+use crate::game_history::{DrawRules, GameHistory, GameResult};
+use crate::notation::format_action;
+use crate::opening_book::OpeningBook;
+use crate::player::Player;
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::fmt::Write;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
+
+// The work done by the players' searches, which gives the speed of the search itself rather than
+// of the whole game.
+#[derive(Default)]
+pub struct SearchStats {
+    pub nodes: usize,
+    pub elapsed_ms: f64,
+}
+
+// One finished game. The log holds what verbose mode prints about the game, along with any
+// forfeit, so that games played at the same time don't interleave their output.
+pub struct GameRecord {
+    pub result: GameResult,
+    pub turn_count: usize,
+    pub search_stats: SearchStats,
+    pub log: String,
+}
+
+#[derive(Default)]
+pub struct Totals {
+    pub game_count: usize,
+    pub wins: [usize; 2],
+    pub draws: usize,
+    pub turn_count: usize,
+    pub search_stats: SearchStats,
+}
+
+pub struct SimulationOptions {
+    pub game_count: usize,
+    pub job_count: usize,
+    pub seed: u64,
+    pub draw_rules: DrawRules,
+    pub verbose: bool,
+    // Verbose mode draws the boards with this player's distances to their goal.
+    pub distance_player_index: Option<usize>,
+}
+
+// Plays the games on a pool of worker threads, each of which creates its own players. Every game
+// gets its own seed for the players and the opening book, derived from `seed` and the game's
+// index, so the same games are played whichever worker plays them. The callback is called on the
+// calling thread as each game finishes, in the order they finish.
+pub fn play_games(
+    create_players: &(dyn Fn() -> [Box<dyn Player>; 2] + Sync),
+    opening_book: Option<&OpeningBook>,
+    options: &SimulationOptions,
+    on_game_end: &mut dyn FnMut(usize, &GameRecord),
+) -> Totals {
+    let SimulationOptions {
+        game_count,
+        job_count,
+        seed,
+        draw_rules,
+        verbose,
+        distance_player_index,
+    } = *options;
+    let next_game = AtomicUsize::new(0);
+    let mut totals = Totals::default();
+    thread::scope(|scope| {
+        let (sender, receiver) = mpsc::channel();
+        for _ in 0..job_count.clamp(1, game_count.max(1)) {
+            let sender = sender.clone();
+            let next_game = &next_game;
+            scope.spawn(move || {
+                let mut players = create_players();
+                loop {
+                    let game_index = next_game.fetch_add(1, Ordering::Relaxed);
+                    if game_index >= game_count {
+                        break;
+                    }
+                    let mut rng = StdRng::seed_from_u64(get_game_seed(seed, game_index));
+                    let record = play_game(
+                        &mut players,
+                        opening_book,
+                        draw_rules,
+                        &mut rng,
+                        verbose,
+                        distance_player_index,
+                    );
+                    if sender.send((game_index, record)).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+        drop(sender);
+
+        for (game_index, record) in receiver {
+            totals.game_count += 1;
+            match record.result {
+                GameResult::Win(winner) => totals.wins[winner] += 1,
+                GameResult::Draw => totals.draws += 1,
+            }
+            totals.turn_count += record.turn_count;
+            totals.search_stats.nodes += record.search_stats.nodes;
+            totals.search_stats.elapsed_ms += record.search_stats.elapsed_ms;
+            on_game_end(game_index, &record);
+        }
+    });
+    totals
+}
+
+fn get_game_seed(seed: u64, game_index: usize) -> u64 {
+    seed.wrapping_add(game_index as u64)
+}
+
+// Plays a game until it is won or drawn. A player that fails to choose an action forfeits the game.
+pub fn play_game(
+    players: &mut [Box<dyn Player>; 2],
+    opening_book: Option<&OpeningBook>,
+    draw_rules: DrawRules,
+    rng: &mut StdRng,
+    verbose: bool,
+    distance_player_index: Option<usize>,
+) -> GameRecord {
+    for player in players.iter_mut() {
+        player.set_seed(rng.gen());
+    }
+    let mut board_state = BoardState::new();
+    let mut player_index = 0;
+    let mut history = GameHistory::new(draw_rules, &board_state, player_index);
+    let mut record = GameRecord {
+        result: GameResult::Draw,
+        turn_count: 0,
+        search_stats: SearchStats::default(),
+        log: String::new(),
+    };
+    loop {
+        let book_action = opening_book.and_then(|book| book.probe_with_rng(&board_state, player_index, rng));
+        let result = match book_action {
+            Some(action) => Ok(action),
+            None => players[player_index].choose_action(&board_state, player_index),
+        };
+        let action = match result {
+            Ok(action) => action,
+            Err(error) => {
+                let _ = writeln!(record.log, "Player {} forfeits: {}", player_index + 1, error);
+                record.result = GameResult::Win(1 - player_index);
+                return record;
+            }
+        };
+        action.apply(&mut board_state, player_index);
+        history.record(&board_state, 1 - player_index);
+        record.turn_count += 1;
+        let report = match book_action {
+            Some(_) => None,
+            None => players[player_index].get_last_report(),
+        };
+        if let Some(report) = report {
+            record.search_stats.nodes += report.nodes;
+            record.search_stats.elapsed_ms += report.elapsed_ms;
+        }
+        if verbose {
+            let _ = writeln!(
+                record.log,
+                "{}. Player {}: {}",
+                record.turn_count,
+                player_index + 1,
+                format_action(&action)
+            );
+            if let Some(report) = report {
+                let _ = writeln!(record.log, "{}", report);
+            }
+            let _ = match distance_player_index {
+                Some(index) => writeln!(record.log, "{}\n", board_state.display_with_distances(index)),
+                None => writeln!(record.log, "{}\n", board_state),
+            };
+        }
+        if let Some(result) = history.get_result(&board_state) {
+            if verbose && result == GameResult::Draw {
+                let _ = writeln!(record.log, "Drawn after {} plies", history.get_ply_count());
+            }
+            record.result = result;
+            return record;
+        }
+        player_index = 1 - player_index;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::minimax_player::MinimaxPlayer;
+    use crate::random_player::RandomPlayer;
+    use crate::shortest_path_player::ShortestPathPlayer;
+
+    fn create_players() -> [Box<dyn Player>; 2] {
+        [Box::new(RandomPlayer::new(0.5)), Box::new(ShortestPathPlayer::new(0.5))]
+    }
+
+    fn get_options(game_count: usize, job_count: usize, seed: u64) -> SimulationOptions {
+        SimulationOptions {
+            game_count,
+            job_count,
+            seed,
+            draw_rules: DrawRules::default(),
+            verbose: false,
+            distance_player_index: None,
+        }
+    }
+
+    #[test]
+    fn totals_do_not_depend_on_job_count() {
+        let play = |job_count| {
+            let mut turn_counts = vec![0; 12];
+            let totals = play_games(
+                &create_players,
+                None,
+                &get_options(12, job_count, 7),
+                &mut |index, record| turn_counts[index] = record.turn_count,
+            );
+            (totals.wins, totals.draws, totals.turn_count, turn_counts)
+        };
+
+        let expected = play(1);
+
+        assert_eq!(expected, play(3));
+        assert_eq!(expected, play(12));
+    }
+
+    #[test]
+    fn seed_changes_games() {
+        let play = |seed| {
+            let mut turn_counts = vec![0; 8];
+            play_games(&create_players, None, &get_options(8, 2, seed), &mut |index, record| {
+                turn_counts[index] = record.turn_count
+            });
+            turn_counts
+        };
+
+        assert_eq!(play(1), play(1));
+        assert_ne!(play(1), play(2));
+    }
+
+    #[test]
+    fn counts_draws() {
+        let mut options = get_options(4, 2, 0);
+        options.draw_rules.max_ply_count = 6;
+
+        let totals = play_games(&create_players, None, &options, &mut |_, record| {
+            assert_eq!(GameResult::Draw, record.result);
+            assert_eq!(6, record.turn_count);
+        });
+
+        assert_eq!([0, 0], totals.wins);
+        assert_eq!(4, totals.draws);
+    }
+
+    #[test]
+    fn reports_search_work() {
+        let create_players =
+            || -> [Box<dyn Player>; 2] { [Box::new(MinimaxPlayer::new(1)), Box::new(ShortestPathPlayer::new(0.5))] };
+        let mut options = get_options(2, 2, 0);
+        options.verbose = true;
+        let mut completed = Vec::new();
+
+        let totals = play_games(&create_players, None, &options, &mut |index, record| {
+            assert_eq!(false, record.log.is_empty());
+            completed.push(index);
+        });
+
+        completed.sort();
+        assert_eq!(vec![0, 1], completed);
+        assert_eq!(2, totals.game_count);
+        assert_eq!(2, totals.wins[0] + totals.wins[1] + totals.draws);
+        assert_eq!(true, totals.search_stats.nodes > 0);
+    }
+
+    #[test]
+    fn verbose_boards_show_distances() {
+        let create_players = || -> [Box<dyn Player>; 2] {
+            [
+                Box::new(ShortestPathPlayer::new(1.0)),
+                Box::new(ShortestPathPlayer::new(1.0)),
+            ]
+        };
+        let mut options = get_options(1, 1, 0);
+        options.verbose = true;
+        options.distance_player_index = Some(1);
+
+        play_games(&create_players, None, &options, &mut |_, record| {
+            // Once the first player steps off the first rank it is all goal for the second.
+            assert_eq!(true, record.log.contains("1 | 0   0   0   0   0   0   0   0   0 | 1"));
+        });
+    }
+}
@@ -127,6 +127,25 @@ pub fn get_valid_block_actions(board_state: &BoardState, player_index: usize) ->
     return actions;
 }
 
+// Every action the player can take, or none once either player has reached their goal.
+pub fn get_legal_actions(board_state: &BoardState, player_index: usize) -> Vec<Action> {
+    if board_state.get_player_distance(0) == 0 || board_state.get_player_distance(1) == 0 {
+        return Vec::new();
+    }
+    let mut actions = get_valid_move_actions(board_state, player_index);
+    actions.append(&mut get_valid_block_actions(board_state, player_index));
+    actions.retain(|action| validate_action(board_state, player_index, action));
+    actions
+}
+
+// Much cheaper than collecting the legal actions since there is almost always a move.
+pub fn has_legal_action(board_state: &BoardState, player_index: usize) -> bool {
+    !get_valid_move_positions(board_state, player_index).is_empty()
+        || get_valid_block_actions(board_state, player_index)
+            .iter()
+            .any(|action| validate_action(board_state, player_index, action))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -137,8 +156,8 @@ mod tests {
         board_state.set_wall(Vector2::new(2, 0), WallOrientation::Vertical);
         board_state.set_wall(Vector2::new(4, 0), WallOrientation::Vertical);
 
-        assert_eq!(true, is_player_trapped(&board_state, 0));
-        assert_eq!(true, is_either_player_trapped(&board_state));
+        assert_eq!(true, is_player_trapped(&board_state, 0), "\n{}", board_state);
+        assert_eq!(true, is_either_player_trapped(&board_state), "\n{}", board_state);
     }
     #[test]
     fn player_2_is_trapped() {
@@ -147,24 +166,24 @@ mod tests {
         board_state.set_wall(Vector2::new(2, 7), WallOrientation::Vertical);
         board_state.set_wall(Vector2::new(4, 7), WallOrientation::Vertical);
 
-        assert_eq!(true, is_player_trapped(&board_state, 1));
-        assert_eq!(true, is_either_player_trapped(&board_state));
+        assert_eq!(true, is_player_trapped(&board_state, 1), "\n{}", board_state);
+        assert_eq!(true, is_either_player_trapped(&board_state), "\n{}", board_state);
     }
 
     #[test]
     fn player_1_is_not_trapped() {
         let board_state = BoardState::new();
 
-        assert_eq!(false, is_player_trapped(&board_state, 0));
-        assert_eq!(false, is_either_player_trapped(&board_state));
+        assert_eq!(false, is_player_trapped(&board_state, 0), "\n{}", board_state);
+        assert_eq!(false, is_either_player_trapped(&board_state), "\n{}", board_state);
     }
 
     #[test]
     fn player_2_is_not_trapped() {
         let board_state = BoardState::new();
 
-        assert_eq!(false, is_player_trapped(&board_state, 1));
-        assert_eq!(false, is_either_player_trapped(&board_state));
+        assert_eq!(false, is_player_trapped(&board_state, 1), "\n{}", board_state);
+        assert_eq!(false, is_either_player_trapped(&board_state), "\n{}", board_state);
     }
 
     #[test]
@@ -172,7 +191,7 @@ mod tests {
         let board_state = BoardState::new();
         let action = Action::Move(Vector2::new(6, 0));
 
-        assert_eq!(false, validate_action(&board_state, 0, &action));
+        assert_eq!(false, validate_action(&board_state, 0, &action), "\n{}", board_state);
     }
 
     #[test]
@@ -181,7 +200,7 @@ mod tests {
         board_state.set_player_wall_count(0, 0);
         let action = Action::Block(Vector2::new(0, 0), WallOrientation::Horizontal);
 
-        assert_eq!(false, validate_action(&board_state, 0, &action));
+        assert_eq!(false, validate_action(&board_state, 0, &action), "\n{}", board_state);
     }
 
     #[test]
@@ -189,7 +208,7 @@ mod tests {
         let board_state = BoardState::new();
         let action = Action::Block(Vector2::new(-1, 0), WallOrientation::Horizontal);
 
-        assert_eq!(false, validate_action(&board_state, 0, &action));
+        assert_eq!(false, validate_action(&board_state, 0, &action), "\n{}", board_state);
     }
 
     #[test]
@@ -198,7 +217,7 @@ mod tests {
         board_state.set_wall(Vector2::new(3, 7), WallOrientation::Horizontal);
         let action = Action::Block(Vector2::new(3, 7), WallOrientation::Horizontal);
 
-        assert_eq!(false, validate_action(&board_state, 0, &action));
+        assert_eq!(false, validate_action(&board_state, 0, &action), "\n{}", board_state);
     }
 
     #[test]
@@ -207,7 +226,7 @@ mod tests {
         board_state.set_wall(Vector2::new(4, 7), WallOrientation::Horizontal);
         let action = Action::Block(Vector2::new(3, 7), WallOrientation::Horizontal);
 
-        assert_eq!(false, validate_action(&board_state, 0, &action));
+        assert_eq!(false, validate_action(&board_state, 0, &action), "\n{}", board_state);
     }
 
     #[test]
@@ -217,7 +236,7 @@ mod tests {
         board_state.set_wall(Vector2::new(2, 0), WallOrientation::Vertical);
         let action = Action::Block(Vector2::new(4, 0), WallOrientation::Vertical);
 
-        assert_eq!(false, validate_action(&board_state, 0, &action));
+        assert_eq!(false, validate_action(&board_state, 0, &action), "\n{}", board_state);
     }
 
     #[test]
@@ -227,7 +246,7 @@ mod tests {
         board_state.set_wall(Vector2::new(2, 7), WallOrientation::Vertical);
         let action = Action::Block(Vector2::new(4, 7), WallOrientation::Vertical);
 
-        assert_eq!(false, validate_action(&board_state, 0, &action));
+        assert_eq!(false, validate_action(&board_state, 0, &action), "\n{}", board_state);
     }
 
     #[test]
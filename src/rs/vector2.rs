@@ -1,8 +1,9 @@
 use serde::Serialize;
 use std::fmt::{Display, Formatter, Result};
 use std::ops::{Add, Mul, Sub};
+use tsify::Tsify;
 
-#[derive(Copy, Clone, Debug, PartialEq, Serialize)]
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Tsify)]
 pub struct Vector2<T> {
     pub x: T,
     pub y: T,
@@ -0,0 +1,160 @@
+use crate::board_state::{BoardState, WallUndoStack};
+use crate::shortest_paths::count_shortest_paths;
+use crate::validation::is_wall_overlapping;
+use crate::vector2::Vector2;
+use crate::wall_orientation::WallOrientation;
+
+use serde::Serialize;
+use tsify::Tsify;
+
+// What placing a wall would do to each player's route to their goal. The distance changes are null
+// for a player the wall would leave with no way to their goal. A wall cuts a shortest path when it
+// makes the player's distance longer or leaves them fewer shortest paths to choose from. Whether it
+// is legal also depends on the player placing it having a wall left.
+#[derive(Clone, Debug, PartialEq, Serialize, Tsify)]
+pub struct WallImpact {
+    pub position: Vector2<isize>,
+    pub orientation: WallOrientation,
+    pub is_legal: bool,
+    pub distance_changes: [Option<isize>; 2],
+    pub cuts_shortest_path: [bool; 2],
+}
+
+// The impact of every wall that fits on the board between the walls already there, in the order
+// `get_valid_block_actions` generates them. Each wall is placed and taken back on a copy of the
+// board, so only the distances it changes are recalculated.
+pub fn get_wall_impacts(board_state: &BoardState, player_index: usize) -> Vec<WallImpact> {
+    let distances = [board_state.get_player_distance(0), board_state.get_player_distance(1)];
+    let path_counts = [
+        count_shortest_paths(board_state, 0),
+        count_shortest_paths(board_state, 1),
+    ];
+    let has_walls = board_state.get_player_wall_count(player_index) > 0;
+    let mut board_state = *board_state;
+    let mut undo_stack = WallUndoStack::default();
+    let mut impacts = Vec::new();
+    for x in 0..8 {
+        for y in 0..8 {
+            let position = Vector2::new(x, y);
+            for &orientation in [WallOrientation::Vertical, WallOrientation::Horizontal].iter() {
+                if is_wall_overlapping(&board_state, position, orientation) {
+                    continue;
+                }
+                let change_start = undo_stack.len();
+                board_state.set_wall_with_undo(position, orientation, &mut undo_stack);
+                let mut distance_changes = [None; 2];
+                let mut cuts_shortest_path = [false; 2];
+                for i in 0..2 {
+                    let distance = board_state.get_player_distance(i);
+                    if distance >= 0 {
+                        distance_changes[i] = Some(distance - distances[i]);
+                    }
+                    cuts_shortest_path[i] =
+                        distance != distances[i] || count_shortest_paths(&board_state, i) < path_counts[i];
+                }
+                board_state.remove_wall(position, &mut undo_stack, change_start);
+                impacts.push(WallImpact {
+                    position,
+                    orientation,
+                    is_legal: has_walls && distance_changes.iter().all(Option::is_some),
+                    distance_changes,
+                    cuts_shortest_path,
+                });
+            }
+        }
+    }
+    impacts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::action::Action;
+    use crate::notation::parse_position;
+    use crate::validation::{get_valid_block_actions, validate_action};
+
+    fn find_impact(impacts: &[WallImpact], x: isize, y: isize, orientation: WallOrientation) -> &WallImpact {
+        let position = Vector2::new(x, y);
+        impacts
+            .iter()
+            .find(|impact| impact.position == position && impact.orientation == orientation)
+            .unwrap()
+    }
+
+    #[test]
+    fn reports_distance_changes() {
+        let impacts = get_wall_impacts(&BoardState::new(), 0);
+
+        assert_eq!(128, impacts.len());
+        // Both pawns have to step around a wall across the e file at the first rank.
+        let impact = find_impact(&impacts, 3, 0, WallOrientation::Horizontal);
+        assert_eq!(true, impact.is_legal);
+        assert_eq!([Some(1), Some(1)], impact.distance_changes);
+        assert_eq!([true, true], impact.cuts_shortest_path);
+        // A wall off to the side gets in nobody's way.
+        let impact = find_impact(&impacts, 0, 0, WallOrientation::Horizontal);
+        assert_eq!([Some(0), Some(0)], impact.distance_changes);
+        assert_eq!([false, false], impact.cuts_shortest_path);
+    }
+
+    #[test]
+    fn cuts_path_without_changing_distance() {
+        // Player 1 can step over to the f file before or after moving up to the second rank, and a
+        // wall between e2 and f2 leaves only the first way. It also stops player 2 stepping across
+        // from e3 on one of their ways around the other wall.
+        let (board_state, player_index) = parse_position("d2h.e1e9.9.10.2").unwrap();
+
+        let impacts = get_wall_impacts(&board_state, player_index);
+
+        let impact = find_impact(&impacts, 4, 1, WallOrientation::Vertical);
+        assert_eq!([Some(0), Some(0)], impact.distance_changes);
+        assert_eq!([true, true], impact.cuts_shortest_path);
+    }
+
+    #[test]
+    fn trapping_walls_are_illegal() {
+        // The wall to the right of a1 and a2 leaves player 1 one way out, past a2.
+        let (board_state, player_index) = parse_position("a1v.a1e9.9.10.2").unwrap();
+
+        let impacts = get_wall_impacts(&board_state, player_index);
+
+        let impact = find_impact(&impacts, 0, 1, WallOrientation::Horizontal);
+        assert_eq!(false, impact.is_legal);
+        assert_eq!([None, Some(0)], impact.distance_changes);
+        assert_eq!([true, false], impact.cuts_shortest_path);
+    }
+
+    #[test]
+    fn matches_placing_each_wall() {
+        let (board_state, player_index) = parse_position("c7hf7hD2VG3V.d5e6.8.8.2").unwrap();
+
+        let impacts = get_wall_impacts(&board_state, player_index);
+
+        let actions = get_valid_block_actions(&board_state, player_index);
+        assert_eq!(actions.len(), impacts.len());
+        for (impact, action) in impacts.iter().zip(actions) {
+            assert_eq!(Action::Block(impact.position, impact.orientation), action);
+            assert_eq!(validate_action(&board_state, player_index, &action), impact.is_legal);
+            let next_board_state = board_state.from_action(&action, player_index);
+            for i in 0..2 {
+                let distance = next_board_state.get_player_distance(i);
+                if distance >= 0 {
+                    assert_eq!(
+                        Some(distance - board_state.get_player_distance(i)),
+                        impact.distance_changes[i]
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn no_walls_are_legal_without_walls_left() {
+        let (board_state, player_index) = parse_position("-.e1e9.0.10.1").unwrap();
+
+        let impacts = get_wall_impacts(&board_state, player_index);
+
+        assert_eq!(128, impacts.len());
+        assert_eq!(true, impacts.iter().all(|impact| !impact.is_legal));
+    }
+}
@@ -1,12 +1,24 @@
 use serde::Serialize;
 use std::fmt;
+use tsify::Tsify;
 
-#[derive(Copy, Clone, Debug, PartialEq, Serialize)]
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Tsify)]
 pub enum WallOrientation {
     Vertical = 0,
     Horizontal = 1,
 }
 
+impl WallOrientation {
+    // The wasm API numbers orientations the other way round from the enum, 0 for horizontal and 1
+    // for vertical, as `apply_block_action` always has.
+    pub fn get_api_index(self) -> usize {
+        match self {
+            WallOrientation::Horizontal => 0,
+            WallOrientation::Vertical => 1,
+        }
+    }
+}
+
 impl fmt::Display for WallOrientation {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
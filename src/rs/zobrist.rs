@@ -0,0 +1,54 @@
+// Random keys used to hash board positions. The keys are generated from a fixed seed so that
+// hashes are stable between runs and builds, which allows them to be stored on disk.
+pub struct ZobristKeys {
+    pub walls: [[[u64; 2]; 8]; 8],
+    pub player_positions: [[[u64; 9]; 9]; 2],
+    pub player_wall_counts: [[u64; 11]; 2],
+    pub player_to_move: u64,
+}
+
+lazy_static! {
+    pub static ref ZOBRIST_KEYS: ZobristKeys = ZobristKeys::new(0x5155_4f52_4944_4f52);
+}
+
+impl ZobristKeys {
+    fn new(seed: u64) -> Self {
+        let mut state = seed;
+        let mut keys = ZobristKeys {
+            walls: [[[0; 2]; 8]; 8],
+            player_positions: [[[0; 9]; 9]; 2],
+            player_wall_counts: [[0; 11]; 2],
+            player_to_move: 0,
+        };
+        for column in keys.walls.iter_mut() {
+            for cell in column.iter_mut() {
+                for key in cell.iter_mut() {
+                    *key = split_mix(&mut state);
+                }
+            }
+        }
+        for player in keys.player_positions.iter_mut() {
+            for column in player.iter_mut() {
+                for key in column.iter_mut() {
+                    *key = split_mix(&mut state);
+                }
+            }
+        }
+        for player in keys.player_wall_counts.iter_mut() {
+            for key in player.iter_mut() {
+                *key = split_mix(&mut state);
+            }
+        }
+        keys.player_to_move = split_mix(&mut state);
+        keys
+    }
+}
+
+// SplitMix64, chosen because its output is fully specified and doesn't depend on any crate version.
+fn split_mix(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
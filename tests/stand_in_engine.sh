@@ -0,0 +1,68 @@
+#!/bin/sh
+# A minimal engine used to test the external player. It speaks just enough of the engine protocol
+# to play a game by always moving its pawn one row towards its goal. The first argument picks how
+# it answers "go":
+#   forward  with a move towards the goal (the default)
+#   hang     never
+#   illegal  with a move that is never legal for the first player
+#   late     not until "stop" the first time, and then a moment later with an illegal move
+mode=${1:-forward}
+position="-.e1e9.10.10.1"
+stopped_search=no
+
+move_forward() {
+    pawns=$(echo "$position" | cut -d. -f2)
+    if [ "$(echo "$position" | cut -d. -f5)" = "1" ]; then
+        column=$(echo "$pawns" | cut -c1)
+        row=$(($(echo "$pawns" | cut -c2) + 1))
+    else
+        column=$(echo "$pawns" | cut -c3)
+        row=$(($(echo "$pawns" | cut -c4) - 1))
+    fi
+    echo "bestmove $column$row"
+}
+
+while read -r command rest; do
+    case "$command" in
+        qei)
+            echo "id name stand-in"
+            echo "qeiok"
+            ;;
+        isready)
+            echo "readyok"
+            ;;
+        position)
+            position=${rest%% *}
+            if [ "$position" = "startpos" ]; then
+                position="-.e1e9.10.10.1"
+            fi
+            ;;
+        go)
+            case "$mode" in
+                hang)
+                    ;;
+                illegal)
+                    echo "bestmove a1"
+                    ;;
+                late)
+                    if [ "$stopped_search" = "yes" ]; then
+                        move_forward
+                    fi
+                    ;;
+                *)
+                    move_forward
+                    ;;
+            esac
+            ;;
+        stop)
+            if [ "$mode" = "late" ] && [ "$stopped_search" = "no" ]; then
+                stopped_search=yes
+                sleep 0.2
+                echo "bestmove a1"
+            fi
+            ;;
+        quit)
+            exit 0
+            ;;
+    esac
+done